@@ -1,43 +1,258 @@
 use crate::error::{ConfigError, Result};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::path::PathBuf;
-
-#[derive(Debug, Serialize, Deserialize, Default)]
-pub struct ConfigFile {
-    #[serde(default)]
-    pub global: HashMap<String, toml::Value>,
-    #[serde(default)]
-    pub model: HashMap<String, toml::Value>,
-    #[serde(default, rename = "feature")]
-    pub features: HashMap<String, HashMap<String, toml::Value>>,
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use toml_edit::{DocumentMut, InlineTable, Item, Key, Table, TableLike, Value};
+
+/// Name of the configuration file inside the .c2rust directory
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Name of the staging copy used while a transaction is open
+pub const STAGING_FILE: &str = "config.toml.staging";
+
+/// Name of a backup copy of config.toml; timestamped backups add a suffix, e.g. `config.toml.bak.20240101`
+pub const BACKUP_FILE: &str = "config.toml.bak";
+
+/// Directory that marks a repository root and stops the upward search for .c2rust
+pub const REPO_BOUNDARY: &str = ".git";
+
+/// Environment variable naming an additional boundary marker file or directory
+pub const BOUNDARY_ENV: &str = "C2RUST_BOUNDARY";
+
+/// Optional top-level table listing the keys allowed in each section
+pub const SCHEMA_SECTION: &str = "schema";
+
+/// Entry of the `[schema]` section listing key patterns whose arrays must not hold duplicates
+pub const NO_DUPLICATES_RULE: &str = "no-duplicates";
+
+/// Key patterns whose arrays must never hold duplicates, whatever the schema says
+pub const BUILTIN_NO_DUPLICATES: [&str; 1] = ["build.files.*"];
+
+/// Entry of the `[schema]` section listing `section.key` patterns that `--set` always stores as arrays
+pub const ALWAYS_ARRAY_RULE: &str = "always-array";
+
+/// `section.key` patterns that are arrays by convention, even when set to a single value
+pub const BUILTIN_ALWAYS_ARRAY: [&str; 1] = ["global.compiler"];
+
+/// The .c2rust directory found by [`Config::resolve_dir`], keyed by the canonical directory
+/// the search started from
+static RESOLVED_DIRS: Mutex<BTreeMap<PathBuf, PathBuf>> = Mutex::new(BTreeMap::new());
+
+/// Content written when config.toml does not exist yet
+const DEFAULT_CONTENT: &str = "[global]\n\n[model]\n\n[feature.default]\n";
+
+/// A `build.files.N` group paired with its `build.options[N]` entry
+#[derive(Debug)]
+pub struct BuildGroup {
+    pub index: usize,
+    pub files: Option<Vec<String>>,
+    pub options: Option<String>,
+}
+
+/// A scalar TOML value, or the rendered text of a value that has no scalar form
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scalar {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Datetime(String),
+    /// A nested array or inline table, rendered as TOML
+    Other(String),
+}
+
+impl Scalar {
+    fn from_value(value: &Value) -> Self {
+        match value {
+            Value::String(s) => Scalar::String(s.value().clone()),
+            Value::Integer(i) => Scalar::Integer(*i.value()),
+            Value::Float(f) => Scalar::Float(*f.value()),
+            Value::Boolean(b) => Scalar::Bool(*b.value()),
+            Value::Datetime(d) => Scalar::Datetime(d.value().to_string()),
+            other => Scalar::Other(Config::value_to_string(other)),
+        }
+    }
+}
+
+impl std::fmt::Display for Scalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scalar::String(s) | Scalar::Datetime(s) | Scalar::Other(s) => f.write_str(s),
+            Scalar::Integer(i) => write!(f, "{}", i),
+            Scalar::Float(x) => write!(f, "{}", Value::from(*x)),
+            Scalar::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// Typed value of an [`Entry`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryValue {
+    Scalar(Scalar),
+    Array(Vec<Scalar>),
+}
+
+impl EntryValue {
+    /// The value as listed by `--list`: one string per array element
+    pub fn to_strings(&self) -> Vec<String> {
+        match self {
+            EntryValue::Scalar(scalar) => vec![scalar.to_string()],
+            EntryValue::Array(elements) => elements.iter().map(Scalar::to_string).collect(),
+        }
+    }
+}
+
+/// A key of a section with its typed value, as returned by [`Config::entries`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: EntryValue,
+    /// Whether the key was flattened from a nested table or inline table in config.toml
+    pub nested: bool,
+}
+
+/// How a key differs between two configs, as reported by [`Config::diff_against`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// One key that differs between two configs
+/// `old` is the value in the config compared against and `new` the value in `self`;
+/// each is `None` when the key is absent on that side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub section: String,
+    pub key: String,
+    pub kind: DiffKind,
+    pub old: Option<Vec<String>>,
+    pub new: Option<Vec<String>>,
+}
+
+/// How [`Config::merge_document`] treats keys that exist on both sides
+/// Keys missing from the config are always added
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MergePolicy {
+    /// Replace existing values with the incoming ones
+    pub overwrite: bool,
+    /// Arrays present on both sides become the incoming elements followed by the existing ones,
+    /// whether or not `overwrite` is set
+    pub concat_arrays: bool,
+    /// Only add missing keys and leave every existing key untouched; takes precedence
+    /// over `overwrite` and `concat_arrays`
+    pub missing_only: bool,
+}
+
+/// Summary of a [`Config::merge_document`] call
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Number of keys added or changed
+    pub written: usize,
+    /// `section.key` entries left untouched because they already existed
+    pub skipped: Vec<String>,
 }
 
+/// TOML scalar type that `--set` stores its values as
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ValueType {
+    #[default]
+    String,
+    Int,
+    Bool,
+    Float,
+}
+
+impl ValueType {
+    /// Parse a raw command-line value into a TOML value of this type
+    pub fn parse(self, raw: &str) -> Result<Value> {
+        let invalid = |type_name: &str| {
+            ConfigError::InvalidOperation(format!("'{}' is not a valid {}", raw, type_name))
+        };
+        Ok(match self {
+            ValueType::String => Value::from(raw),
+            ValueType::Int => Value::from(raw.parse::<i64>().map_err(|_| invalid("int"))?),
+            ValueType::Bool => Value::from(raw.parse::<bool>().map_err(|_| invalid("bool"))?),
+            ValueType::Float => Value::from(raw.parse::<f64>().map_err(|_| invalid("float"))?),
+        })
+    }
+
+    /// Guess the TOML type of a raw value: plain integers, decimal floats and
+    /// `true`/`false` are typed, anything else (including leading zeros) stays a string
+    pub fn infer(raw: &str) -> Value {
+        let unsigned = raw.strip_prefix('-').unwrap_or(raw);
+        let (whole, fraction) = match unsigned.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (unsigned, None),
+        };
+        let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        let numeric = is_digits(whole)
+            && (whole == "0" || !whole.starts_with('0'))
+            && fraction.is_none_or(is_digits);
+
+        match (raw, numeric, fraction) {
+            ("true", _, _) => Value::from(true),
+            ("false", _, _) => Value::from(false),
+            (_, true, None) => raw.parse::<i64>().map(Value::from).unwrap_or_else(|_| Value::from(raw)),
+            (_, true, Some(_)) => raw.parse::<f64>().map(Value::from).unwrap_or_else(|_| Value::from(raw)),
+            _ => Value::from(raw),
+        }
+    }
+}
+
+/// Configuration backed by a format-preserving TOML document
+/// Keys keep the order in which they appear in config.toml
+///
+/// Sections are addressed by name: `"global"`, `"model"`, or `"feature.<name>"`.
+/// Mutations only touch the in-memory document until [`Config::save`] is called.
 pub struct Config {
     config_path: PathBuf,
-    data: ConfigFile,
+    document: DocumentMut,
+    /// `(section, key)` pairs that were flattened from nested tables when loading
+    nested: BTreeSet<(String, String)>,
+    /// Whether most lines of the loaded file ended in `\r\n`, so saving writes CRLF too
+    crlf: bool,
+    /// Whether the loaded file ended with a newline (an empty file counts as ending with one)
+    trailing_newline: bool,
 }
 
 impl Config {
     /// Find .c2rust directory by traversing up from current directory
-    /// Searches from current working directory up to root, looking for .c2rust directory
-    fn find_c2rust_dir() -> Result<PathBuf> {
+    /// Searches from current working directory up to root, looking for .c2rust directory.
+    /// The search stops at a repository boundary (a `.git` entry, or the marker named by
+    /// `C2RUST_BOUNDARY`) so an unrelated `.c2rust` in a parent checkout is never picked up.
+    pub(crate) fn find_c2rust_dir() -> Result<PathBuf> {
         let search_start = std::env::current_dir()?;
+        let mut markers = vec![REPO_BOUNDARY.to_string()];
+        markers.extend(std::env::var(BOUNDARY_ENV).ok().filter(|marker| !marker.is_empty()));
         let mut current = search_start.clone();
-        
+
         loop {
             let c2rust_path = current.join(".c2rust");
             if c2rust_path.exists() && c2rust_path.is_dir() {
                 return Ok(c2rust_path);
             }
-            
+
+            // Do not leave the current repository
+            if markers.iter().any(|marker| current.join(marker).exists()) {
+                return Err(ConfigError::ConfigDirNotFound {
+                    start: search_start,
+                    boundary: Some(current),
+                });
+            }
+
             // Try to move to parent directory
             match current.parent() {
                 Some(parent) => current = parent.to_path_buf(),
                 None => {
                     // Reached root without finding .c2rust directory
-                    return Err(ConfigError::ConfigDirNotFound(search_start));
+                    return Err(ConfigError::ConfigDirNotFound {
+                        start: search_start,
+                        boundary: None,
+                    });
                 }
             }
         }
@@ -45,212 +260,1321 @@ impl Config {
 
     /// Flatten nested table structures into dotted keys (recursively)
     /// Converts structures like:
-    ///   clean = Table({"cmd": "make clean", "dir": "build"})
+    ///   [feature.default.clean]
+    ///   cmd = "make clean"
     /// Into:
-    ///   "clean.cmd" = "make clean", "clean.dir" = "build"
-    /// Also handles deeply nested structures:
-    ///   build = Table({"options": Table({"debug": true})})
+    ///   "clean.cmd" = "make clean"
+    /// Also handles deeply nested structures and inline tables:
+    ///   build = { options = { debug = true } }
     /// Into:
     ///   "build.options.debug" = true
-    fn flatten_table(table: &mut HashMap<String, toml::Value>) {
-        fn flatten_value(prefix: &str, value: &toml::Value, result: &mut HashMap<String, toml::Value>) {
-            if let Some(nested_table) = value.as_table() {
-                // Recursively flatten nested tables
-                for (nested_key, nested_value) in nested_table {
-                    let new_prefix = if prefix.is_empty() {
-                        nested_key.clone()
-                    } else {
-                        format!("{}.{}", prefix, nested_key)
-                    };
-                    flatten_value(&new_prefix, nested_value, result);
+    /// Flattened keys take the position of the table they came from, so document order is kept,
+    /// and the comment above a `[feature.default.build]` header or an inline table moves to the
+    /// first key that came from it
+    /// Returns the keys that came from sub-tables or inline tables; bare dotted keys such as
+    /// `build.dir = "build"` are the normal written form and are not counted
+    fn flatten_table(table: &mut Table) -> Vec<String> {
+        // `key` supplies the comments of the leaf; `prefix` is the full dotted name so far;
+        // `comment` holds a table's leading comment until its first leaf is inserted
+        fn flatten_item(
+            prefix: &str,
+            key: &Key,
+            item: Item,
+            table: &mut Table,
+            nested: bool,
+            comment: &mut String,
+            flattened: &mut Vec<String>,
+        ) {
+            let decor_prefix = |decor: &toml_edit::Decor| decor.prefix().and_then(|raw| raw.as_str()).unwrap_or("").to_string();
+            // Only a prefix with a comment is carried; the blank line before a header is not
+            let carry = |comment: &mut String, decor: &toml_edit::Decor| {
+                let prefix = decor_prefix(decor);
+                if prefix.contains('#') {
+                    comment.push_str(&prefix);
+                }
+            };
+            match item {
+                Item::Table(mut sub_table) => {
+                    let nested = nested || !sub_table.is_dotted();
+                    carry(comment, sub_table.decor());
+                    let sub_keys: Vec<String> = sub_table.iter().map(|(sub_key, _)| sub_key.to_string()).collect();
+                    for sub_key in sub_keys {
+                        if let Some((sub_key, sub_item)) = sub_table.remove_entry(&sub_key) {
+                            let full = format!("{}.{}", prefix, sub_key.get());
+                            flatten_item(&full, &sub_key, sub_item, table, nested, comment, flattened);
+                        }
+                    }
+                }
+                Item::Value(Value::InlineTable(sub_table)) => {
+                    carry(comment, key.leaf_decor());
+                    for (sub_key, mut sub_value) in sub_table {
+                        // The spacing around a value inside `{ ... }` does not belong on its own line
+                        sub_value.decor_mut().clear();
+                        let full = format!("{}.{}", prefix, sub_key);
+                        flatten_item(&full, &Key::new(&full), Item::Value(sub_value), table, true, comment, flattened);
+                    }
                 }
-            } else {
                 // Leaf value - add it to the result
-                result.insert(prefix.to_string(), value.clone());
+                leaf => {
+                    let mut decor = key.leaf_decor().clone();
+                    if !comment.is_empty() {
+                        decor.set_prefix(std::mem::take(comment) + &decor_prefix(key.leaf_decor()));
+                    }
+                    table.insert_formatted(&Key::new(prefix).with_leaf_decor(decor), leaf);
+                    if nested {
+                        flattened.push(prefix.to_string());
+                    }
+                }
             }
         }
 
-        let mut flattened = HashMap::new();
-        let mut keys_to_remove = Vec::new();
+        let mut flattened = Vec::new();
+        if !table.iter().any(|(_, item)| item.is_table_like()) {
+            return flattened;
+        }
 
-        for (key, value) in table.iter() {
-            if value.as_table().is_some() {
-                // This is a nested table, flatten it recursively
-                keys_to_remove.push(key.clone());
-                flatten_value(key, value, &mut flattened);
+        // Re-insert every entry in its original order, expanding nested tables in place
+        let keys: Vec<String> = table.iter().map(|(key, _)| key.to_string()).collect();
+        for key in keys {
+            if let Some((key, item)) = table.remove_entry(&key) {
+                if item.is_table_like() {
+                    flatten_item(key.get(), &key, item, table, false, &mut String::new(), &mut flattened);
+                } else {
+                    table.insert_formatted(&key, item);
+                }
             }
         }
+        flattened
+    }
+
+    /// Make sure a top-level section exists and is a table
+    fn ensure_section(document: &mut DocumentMut, section: &str) -> Result<()> {
+        let item = document.entry(section).or_insert_with(|| Item::Table(Table::new()));
+        if item.is_table() {
+            Ok(())
+        } else {
+            Err(ConfigError::TomlParseError(format!("'{}' must be a table", section)))
+        }
+    }
 
-        // Remove the nested table keys
-        for key in keys_to_remove {
-            table.remove(&key);
+    /// Resolve the .c2rust directory for the current directory
+    /// The result is reused by later calls from the same directory, so changing the current
+    /// directory resolves again; a failed search is not cached
+    pub fn resolve_dir() -> Result<PathBuf> {
+        let cwd = fs::canonicalize(std::env::current_dir()?)?;
+        let mut resolved = RESOLVED_DIRS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(dir) = resolved.get(&cwd).filter(|dir| dir.is_dir()) {
+            return Ok(dir.clone());
         }
+        let dir = Self::find_c2rust_dir()?;
+        resolved.insert(cwd, dir.clone());
+        Ok(dir)
+    }
 
-        // Add the flattened keys
-        table.extend(flattened);
+    /// Resolve the config file the current directory operates on, without creating it
+    /// While a transaction is open, this is the staging copy so every operation
+    /// reads and writes the staged state
+    pub fn resolve_path() -> Result<PathBuf> {
+        let c2rust_dir = Self::resolve_dir()?;
+        let staging_path = c2rust_dir.join(STAGING_FILE);
+        if staging_path.exists() {
+            Ok(staging_path)
+        } else {
+            Ok(c2rust_dir.join(CONFIG_FILE))
+        }
     }
 
     /// Load configuration from file
     /// Auto-creates config.toml if it doesn't exist
     pub fn load() -> Result<Self> {
-        let c2rust_dir = Self::find_c2rust_dir()?;
-        let config_path = c2rust_dir.join("config.toml");
+        let config_path = Self::resolve_path()?;
+
+        if !config_path.exists() {
+            // Auto-create config.toml with default sections including feature.default
+            fs::write(&config_path, DEFAULT_CONTENT)?;
+        }
+
+        Self::load_from(&config_path)
+    }
+
+    /// The most recently modified backup of config.toml in the .c2rust directory:
+    /// `config.toml.bak` or any `config.toml.bak.<suffix>`
+    pub fn latest_backup() -> Result<PathBuf> {
+        let c2rust_dir = Self::resolve_dir()?;
+        let mut latest: Option<(std::time::SystemTime, PathBuf)> = None;
+        for entry in fs::read_dir(&c2rust_dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let is_backup = name == BACKUP_FILE
+                || name.strip_prefix(BACKUP_FILE).is_some_and(|suffix| suffix.starts_with('.'));
+            if !is_backup || !entry.file_type()?.is_file() {
+                continue;
+            }
+            let modified = entry.metadata()?.modified()?;
+            if latest.as_ref().is_none_or(|(time, _)| modified > *time) {
+                latest = Some((modified, entry.path()));
+            }
+        }
+        latest.map(|(_, path)| path).ok_or_else(|| {
+            ConfigError::InvalidOperation(format!(
+                "no backup found in {} (expected {} or {}.<suffix>)",
+                c2rust_dir.display(),
+                BACKUP_FILE,
+                BACKUP_FILE
+            ))
+        })
+    }
 
-        let content = match fs::read_to_string(&config_path) {
+    /// Load the configuration for a read-only operation
+    /// A missing .c2rust directory or config.toml is treated as the default, empty config
+    /// instead of an error, and nothing is created on disk
+    pub fn load_lenient() -> Result<Self> {
+        let config_path = match Self::resolve_path() {
+            Ok(path) => path,
+            Err(ConfigError::ConfigDirNotFound { .. }) => PathBuf::from(".c2rust").join(CONFIG_FILE),
+            Err(e) => return Err(e),
+        };
+        match Self::load_from(&config_path) {
+            Err(ConfigError::ConfigFileNotFound(_)) => Self::parse(config_path, DEFAULT_CONTENT),
+            result => result,
+        }
+    }
+
+    /// Load a specific config.toml without searching for the .c2rust directory
+    /// Changes are saved back to the same path
+    /// Returns `ConfigFileNotFound` if the file does not exist
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let content = match fs::read_to_string(path) {
             Ok(content) => content,
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                // Auto-create config.toml with default sections including feature.default
-                let default_content = "[global]\n\n[model]\n\n[feature.default]\n";
-                fs::write(&config_path, default_content)?;
-                default_content.to_owned()
+                return Err(ConfigError::ConfigFileNotFound(path.to_path_buf()));
             }
             Err(e) => return Err(e.into()),
         };
+        Self::parse(path.to_path_buf(), &content)
+    }
+
+    /// Parse config.toml content and normalize its sections
+    fn parse(config_path: PathBuf, content: &str) -> Result<Self> {
+        let mut document: DocumentMut = content
+            .parse()
+            .map_err(|e| Self::parse_error(&config_path, content, &e))?;
+
+        Self::ensure_section(&mut document, "global")?;
+        Self::ensure_section(&mut document, "model")?;
+
+        let crlf_lines = content.matches("\r\n").count();
+        let crlf = crlf_lines > content.matches('\n').count() - crlf_lines;
+        let trailing_newline = content.is_empty() || content.ends_with('\n');
+        let mut config = Config { config_path, document, nested: BTreeSet::new(), crlf, trailing_newline };
+        config.normalize()?;
+        Ok(config)
+    }
+
+    /// Describe a syntax error as `path:line:column: message` followed by the offending line
+    fn parse_error(path: &Path, content: &str, err: &toml_edit::TomlError) -> ConfigError {
+        let mut offset = err.span().map_or(0, |span| span.start).min(content.len());
+        while !content.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line = content[..offset].matches('\n').count() + 1;
+        let column = content[line_start..offset].chars().count() + 1;
+        let text = content[line_start..].lines().next().unwrap_or("");
+        let gutter = " ".repeat(line.to_string().len());
+
+        ConfigError::TomlParseError(format!(
+            "{}:{}:{}: {}\n{} |\n{} | {}\n{} | {}^",
+            path.display(),
+            line,
+            column,
+            err.message().trim(),
+            gutter,
+            line,
+            text,
+            gutter,
+            " ".repeat(column - 1),
+        ))
+    }
+
+    /// Convert nested sub-tables in every section into dotted keys
+    /// Runs at load time so every operation sees `build.cmd` as a single key,
+    /// whether the file used `[feature.default.build]`, dotted keys or inline tables
+    pub fn normalize(&mut self) -> Result<()> {
+        for (name, item) in self.document.iter_mut() {
+            if name != "feature" {
+                if let Some(table) = item.as_table_mut() {
+                    let flattened = Self::flatten_table(table);
+                    self.nested.extend(flattened.into_iter().map(|key| (name.to_string(), key)));
+                }
+            }
+        }
+        if let Some(features) = self.document.get_mut("feature") {
+            let features = features
+                .as_table_mut()
+                .ok_or_else(|| ConfigError::TomlParseError("'feature' must be a table".to_string()))?;
+            for (name, feature) in features.iter_mut() {
+                let feature_table = feature.as_table_mut().ok_or_else(|| {
+                    ConfigError::TomlParseError(format!("'feature.{}' must be a table", name))
+                })?;
+                let section = format!("feature.{}", name);
+                let flattened = Self::flatten_table(feature_table);
+                self.nested.extend(flattened.into_iter().map(|key| (section.clone(), key)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Turn the flat `"build.dir"` keys of every section into bare dotted keys (`build.dir`),
+    /// the form config.toml is always written in
+    /// A key stays quoted when one of its parts is not a valid bare key, or when it would
+    /// clash with another key of the section, such as `build` next to `build.dir`.
+    /// Keys sharing a first part are written next to each other, at the position of the first of
+    /// them, since toml_edit keeps a dotted table as one block: interleaved `build.dir`,
+    /// `clean.cmd`, `build.cmd` are already read in the order `build.dir`, `build.cmd`, `clean.cmd`
+    /// and saved that way.
+    fn normalize_dotted_keys(document: &mut DocumentMut) {
+        let is_bare = |part: &str| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        };
+
+        let mut tables: Vec<&mut Table> = Vec::new();
+        for (name, item) in document.iter_mut() {
+            let Some(table) = item.as_table_mut() else {
+                continue;
+            };
+            if name == "feature" {
+                tables.extend(table.iter_mut().filter_map(|(_, feature)| feature.as_table_mut()));
+            } else {
+                tables.push(table);
+            }
+        }
+
+        for table in tables {
+            let keys: Vec<String> = table.iter().map(|(key, _)| key.to_string()).collect();
+            let clashes = |key: &str| {
+                keys.iter().any(|other| {
+                    other.starts_with(&format!("{}.", key)) || key.starts_with(&format!("{}.", other))
+                })
+            };
+            if !keys.iter().any(|key| key.contains('.') && key.split('.').all(is_bare) && !clashes(key)) {
+                continue;
+            }
 
-        let mut data: ConfigFile = toml::from_str(&content)?;
-        
-        // Flatten nested structures in all sections
-        Self::flatten_table(&mut data.global);
-        Self::flatten_table(&mut data.model);
-        for (_, feature_table) in data.features.iter_mut() {
-            Self::flatten_table(feature_table);
+            let entries: Vec<(Key, Item)> = keys.iter().filter_map(|key| table.remove_entry(key)).collect();
+            for (key, item) in entries {
+                let dotted = key.get().contains('.') && key.get().split('.').all(is_bare) && !clashes(key.get());
+                if !dotted || item.is_table_like() {
+                    table.insert_formatted(&key, item);
+                    continue;
+                }
+
+                let parts: Vec<&str> = key.get().split('.').collect();
+                let (leaf, path) = parts.split_last().expect("dotted keys have several parts");
+                let mut parent = &mut *table;
+                for part in path {
+                    let child = parent.entry(part).or_insert_with(|| {
+                        let mut dotted_table = Table::new();
+                        dotted_table.set_dotted(true);
+                        Item::Table(dotted_table)
+                    });
+                    parent = child.as_table_mut().expect("only dotted tables are created here");
+                }
+                parent.insert_formatted(&Key::new(*leaf).with_leaf_decor(key.leaf_decor().clone()), item);
+            }
         }
-        
-        Ok(Config { config_path, data })
+    }
+
+    /// Path of the config.toml this configuration was loaded from and saves to
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// The underlying TOML document, after normalization
+    pub fn document(&self) -> &DocumentMut {
+        &self.document
     }
 
     /// Save configuration to file
+    /// Write failures name the target file and explain common causes
     pub fn save(&self) -> Result<()> {
-        let content = toml::to_string_pretty(&self.data)?;
-        fs::write(&self.config_path, content)?;
-        Ok(())
+        self.save_to(&self.config_path)
+    }
+
+    /// Write the configuration to another file, leaving the loaded config.toml untouched
+    /// The line ending and final newline of the loaded file are kept
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let mut document = self.document.clone();
+        Self::normalize_dotted_keys(&mut document);
+        fs::write(path, self.with_line_endings(document.to_string()))
+            .map_err(|e| Self::write_error(path, e))
+    }
+
+    /// Apply the loaded file's line ending and final newline to rendered TOML
+    /// Lines toml_edit adds always end in `\n`, so the text is normalized before CRLF is re-applied
+    fn with_line_endings(&self, text: String) -> String {
+        let mut text = text.replace("\r\n", "\n");
+        if self.trailing_newline {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+        } else {
+            while text.ends_with('\n') {
+                text.pop();
+            }
+        }
+        if self.crlf {
+            text = text.replace('\n', "\r\n");
+        }
+        text
+    }
+
+    /// Wrap an I/O error from writing `path` with the path and a hint about the cause
+    fn write_error(path: &Path, err: std::io::Error) -> ConfigError {
+        use std::io::ErrorKind;
+        let reason = match err.kind() {
+            ErrorKind::PermissionDenied => {
+                "permission denied (check that the file and its directory are writable)".to_string()
+            }
+            ErrorKind::NotFound => {
+                "the file or its directory no longer exists (or the file is a dangling symlink)".to_string()
+            }
+            ErrorKind::ReadOnlyFilesystem => "the file system is read-only".to_string(),
+            ErrorKind::IsADirectory => "the path is a directory".to_string(),
+            _ => err.to_string(),
+        };
+        ConfigError::IoError(std::io::Error::new(
+            err.kind(),
+            format!("failed to write {}: {}", path.display(), reason),
+        ))
     }
 
     /// Get the table for a specific section
-    fn get_table_mut(&mut self, section: &str, create: bool) -> Result<&mut HashMap<String, toml::Value>> {
-        if section == "global" {
-            return Ok(&mut self.data.global);
-        } else if section == "model" {
-            return Ok(&mut self.data.model);
+    fn get_table_mut(&mut self, section: &str, create: bool) -> Result<&mut Table> {
+        if section == "global" || section == "model" {
+            return self.document[section]
+                .as_table_mut()
+                .ok_or_else(|| ConfigError::InvalidOperation(format!("Invalid section: {}", section)));
         } else if let Some(feature_name) = section.strip_prefix("feature.") {
-            if !self.data.features.contains_key(feature_name) {
+            let features = self.document.entry("feature").or_insert_with(|| {
+                let mut features = Table::new();
+                features.set_implicit(true);
+                Item::Table(features)
+            });
+            let features = features
+                .as_table_mut()
+                .ok_or_else(|| ConfigError::InvalidOperation("'feature' is not a table".to_string()))?;
+            if !features.contains_key(feature_name) {
                 if !create {
                     return Err(ConfigError::FeatureNotFound(section.to_string()));
                 }
-                self.data.features.insert(feature_name.to_string(), HashMap::new());
+                let mut feature = Table::new();
+                // Separate the new section header from the previous section
+                feature.decor_mut().set_prefix("\n");
+                features.insert(feature_name, Item::Table(feature));
             }
-            return Ok(self.data.features.get_mut(feature_name).unwrap());
+            return features[feature_name]
+                .as_table_mut()
+                .ok_or_else(|| ConfigError::InvalidOperation(format!("Invalid section: {}", section)));
+        } else if !section.is_empty() && !section.contains('.') {
+            // Any other top-level table, e.g. `[cache]`
+            if !self.document.contains_key(section) {
+                if !create {
+                    return Err(ConfigError::InvalidOperation(format!("section '{}' not found", section)));
+                }
+                let mut table = Table::new();
+                table.decor_mut().set_prefix("\n");
+                self.document.insert(section, Item::Table(table));
+            }
+            return self.document[section]
+                .as_table_mut()
+                .ok_or_else(|| ConfigError::InvalidOperation(format!("Invalid section: {}", section)));
         }
         Err(ConfigError::InvalidOperation(format!("Invalid section: {}", section)))
     }
 
     /// Get the table for reading
-    fn get_table(&self, section: &str) -> Result<&HashMap<String, toml::Value>> {
-        if section == "global" {
-            return Ok(&self.data.global);
-        } else if section == "model" {
-            return Ok(&self.data.model);
+    fn get_table(&self, section: &str) -> Result<&Table> {
+        if section == "global" || section == "model" {
+            return self.document[section]
+                .as_table()
+                .ok_or_else(|| ConfigError::InvalidOperation(format!("Invalid section: {}", section)));
         } else if let Some(feature_name) = section.strip_prefix("feature.") {
-            return self.data.features
-                .get(feature_name)
+            return self.document
+                .get("feature")
+                .and_then(|features| features.get(feature_name))
+                .and_then(Item::as_table)
                 .ok_or_else(|| ConfigError::FeatureNotFound(section.to_string()));
+        } else if !section.is_empty() && !section.contains('.') {
+            return match self.document.get(section) {
+                Some(item) => item
+                    .as_table()
+                    .ok_or_else(|| ConfigError::InvalidOperation(format!("Invalid section: {}", section))),
+                None => Err(ConfigError::InvalidOperation(format!("section '{}' not found", section))),
+            };
         }
         Err(ConfigError::InvalidOperation(format!("Invalid section: {}", section)))
     }
 
-    /// Convert a TOML value to a list of strings
-    fn value_to_strings(value: &toml::Value) -> Vec<String> {
-        if let Some(array) = value.as_array() {
-            array.iter()
-                .map(|item| item.as_str().map(String::from).unwrap_or_else(|| item.to_string()))
-                .collect()
-        } else if let Some(s) = value.as_str() {
-            vec![s.to_string()]
-        } else {
-            vec![value.to_string()]
+    /// Section name of a feature, lowercased and checked to contain only `[a-z0-9._-]`
+    /// `all` is refused, since `--feature all` selects every feature rather than one named `all`
+    pub fn feature_section(name: &str) -> Result<String> {
+        let name = name.to_lowercase();
+        let valid = name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-'));
+        if name.is_empty() || !valid {
+            return Err(ConfigError::InvalidOperation(format!(
+                "invalid feature name '{}': use only a-z, 0-9, '.', '_' and '-'",
+                name
+            )));
+        }
+        if name == "all" {
+            return Err(ConfigError::InvalidOperation(
+                "invalid feature name 'all': --feature all stands for every feature".to_string(),
+            ));
         }
+        Ok(format!("feature.{}", name))
     }
 
-    /// List all keys and values in a section
-    pub fn list_all(&self, section: &str) -> Result<Vec<(String, Vec<String>)>> {
+    /// Section name given to `--section`: `feature.<name>` is checked like [`Config::feature_section`],
+    /// any other section must be a top-level name of only `[a-z0-9_-]`
+    pub fn section_name(section: &str) -> Result<String> {
+        if let Some(name) = section.strip_prefix("feature.") {
+            return Self::feature_section(name);
+        }
+        let valid = section.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-'));
+        if section.is_empty() || !valid {
+            return Err(ConfigError::InvalidOperation(format!(
+                "invalid section name '{}': use only a-z, 0-9, '_' and '-'",
+                section
+            )));
+        }
+        Ok(section.to_string())
+    }
+
+    /// Feature that `--make` targets when no `--feature` is given:
+    /// `default_feature` from `[global]`, or `default` when that is not set
+    pub fn default_feature(&self) -> &str {
+        self.get_table("global")
+            .ok()
+            .and_then(|global| global.get("default_feature"))
+            .and_then(Item::as_str)
+            .unwrap_or("default")
+    }
+
+    /// Names of all `[feature.*]` sections in document order
+    pub fn feature_names(&self) -> Vec<String> {
+        self.document
+            .get("feature")
+            .and_then(Item::as_table)
+            .map(|features| {
+                features.iter()
+                    .filter(|(_, item)| item.is_table())
+                    .map(|(name, _)| name.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Names of every section in document order, with each feature as `feature.<name>`
+    pub fn section_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for (name, item) in self.document.iter() {
+            if name == "feature" {
+                names.extend(self.feature_names().into_iter().map(|feature| format!("feature.{}", feature)));
+            } else if item.is_table() {
+                names.push(name.to_string());
+            }
+        }
+        names
+    }
+
+    /// Render a single TOML value without its surrounding whitespace or comments
+    fn value_to_string(value: &Value) -> String {
+        if let Some(s) = value.as_str() {
+            return s.to_string();
+        }
+        let mut value = value.clone();
+        match &mut value {
+            Value::Array(array) => array.fmt(),
+            Value::InlineTable(table) => table.fmt(),
+            _ => {}
+        }
+        value.decor_mut().clear();
+        value.to_string()
+    }
+
+    /// Convert a TOML item to a list of strings
+    fn value_to_strings(item: &Item) -> Vec<String> {
+        match item {
+            Item::Value(Value::Array(array)) => array.iter().map(Self::value_to_string).collect(),
+            Item::Value(value) => vec![Self::value_to_string(value)],
+            other => vec![other.to_string().trim().to_string()],
+        }
+    }
+
+    /// Every key of a section with its typed value, in document order
+    pub fn entries(&self, section: &str) -> Result<Vec<Entry>> {
         let table = self.get_table(section)?;
-        
+
         Ok(table.iter()
-            .filter_map(|(key, value)| {
-                let values = Self::value_to_strings(value);
-                if values.is_empty() {
-                    None
-                } else {
-                    Some((key.clone(), values))
-                }
+            .map(|(key, item)| Entry {
+                key: key.to_string(),
+                value: match item {
+                    Item::Value(Value::Array(array)) => EntryValue::Array(array.iter().map(Scalar::from_value).collect()),
+                    Item::Value(value) => EntryValue::Scalar(Scalar::from_value(value)),
+                    other => EntryValue::Scalar(Scalar::Other(other.to_string().trim().to_string())),
+                },
+                nested: self.nested.contains(&(section.to_string(), key.to_string())),
             })
             .collect())
     }
 
+    /// List all keys and values in a section as strings, in document order
+    /// Keys holding an empty array are left out
+    pub fn list_all(&self, section: &str) -> Result<Vec<(String, Vec<String>)>> {
+        Ok(self.entries(section)?
+            .into_iter()
+            .map(|entry| (entry.key, entry.value.to_strings()))
+            .filter(|(_, values)| !values.is_empty())
+            .collect())
+    }
+
+    /// List the keys of a section that match a pattern where `*` stands for any run of
+    /// characters, e.g. `build.*`, in document order
+    pub fn list_matching(&self, section: &str, pattern: &str) -> Result<Vec<(String, Vec<String>)>> {
+        let matches: Vec<_> = self.list_all(section)?
+            .into_iter()
+            .filter(|(key, _)| glob_match(pattern, key))
+            .collect();
+        if matches.is_empty() {
+            return Err(ConfigError::KeyNotFound(pattern.to_string()));
+        }
+        Ok(matches)
+    }
+
+    /// Whether a section (`global`, `model`, `feature.<name>` or another top-level table) exists
+    pub fn section_exists(&self, section: &str) -> bool {
+        self.get_table(section).is_ok()
+    }
+
+    /// An existing feature whose name is within one edit of `name`, if any
+    /// Used to catch typos like `debg` for `debug` when a new feature is created; names shorter
+    /// than three characters are never similar, since `a` and `b` are one edit apart as well
+    pub fn similar_feature(&self, name: &str) -> Option<String> {
+        let long_enough = |name: &str| name.chars().count() >= 3;
+        if !long_enough(name) {
+            return None;
+        }
+        self.feature_names()
+            .into_iter()
+            .find(|existing| existing != name && long_enough(existing) && edit_distance(existing, name) <= 1)
+    }
+
+    /// Check a key against the optional `[schema]` section
+    /// The schema maps a section name (or `feature` for every feature) to an array of allowed
+    /// key patterns, where `*` matches any run of characters. Sections without an entry, and
+    /// configs without a schema, allow every key.
+    pub fn schema_allows(&self, section: &str, key: &str) -> bool {
+        let Some(schema) = self.document.get(SCHEMA_SECTION).and_then(Item::as_table) else {
+            return true;
+        };
+        let entry = schema.get(section).or_else(|| {
+            section.strip_prefix("feature.").and_then(|_| schema.get("feature"))
+        });
+        let Some(patterns) = entry.and_then(Item::as_array) else {
+            return true;
+        };
+        patterns.iter().filter_map(Value::as_str).any(|pattern| glob_match(pattern, key))
+    }
+
+    /// Whether the array stored at `key` must not contain duplicate elements, either by the
+    /// built-in rule for `build.files.*` or a pattern in `[schema] no-duplicates`
+    pub fn requires_unique(&self, key: &str) -> bool {
+        let schema_patterns = self.document
+            .get(SCHEMA_SECTION)
+            .and_then(|schema| schema.get(NO_DUPLICATES_RULE))
+            .and_then(Item::as_array)
+            .into_iter()
+            .flat_map(|patterns| patterns.iter().filter_map(Value::as_str));
+        BUILTIN_NO_DUPLICATES.into_iter().chain(schema_patterns).any(|pattern| glob_match(pattern, key))
+    }
+
+    /// Whether `--set` should store `key` as an array even when given a single value, either by
+    /// the built-in convention for `[global] compiler` or a pattern in `[schema] always-array`
+    /// Patterns are matched against `section.key`, e.g. `feature.*.test.args`
+    pub fn always_array(&self, section: &str, key: &str) -> bool {
+        let qualified = format!("{}.{}", section, key);
+        let schema_patterns = self.document
+            .get(SCHEMA_SECTION)
+            .and_then(|schema| schema.get(ALWAYS_ARRAY_RULE))
+            .and_then(Item::as_array)
+            .into_iter()
+            .flat_map(|patterns| patterns.iter().filter_map(Value::as_str));
+        BUILTIN_ALWAYS_ARRAY.into_iter().chain(schema_patterns).any(|pattern| glob_match(pattern, &qualified))
+    }
+
+    /// List the key names of a section, sorted
+    pub fn keys(&self, section: &str) -> Result<Vec<String>> {
+        let table = self.get_table(section)?;
+        let mut keys: Vec<String> = table.iter().map(|(key, _)| key.to_string()).collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Other keys of a section that `key` shadows or is shadowed by, e.g. `build` for `build.dir`
+    /// Such keys cannot be written as one dotted table, so they stay quoted in config.toml
+    pub fn shadowing_keys(&self, section: &str, key: &str) -> Result<Vec<String>> {
+        let table = self.get_table(section)?;
+        Ok(table
+            .iter()
+            .map(|(other, _)| other)
+            .filter(|other| other.starts_with(&format!("{}.", key)) || key.starts_with(&format!("{}.", other)))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// TOML type of a key's stored value, e.g. `string`, `integer` or `array`
+    pub fn type_name(&self, section: &str, key: &str) -> Result<&'static str> {
+        Ok(self.item(section, key)?.type_name())
+    }
+
     /// Get values for a specific key in a section
+    /// A key that only exists through its dotted sub-keys, such as an inline table set with
+    /// `--set --json` and flattened on load, is returned as that inline table
     pub fn list(&self, section: &str, key: &str) -> Result<Vec<String>> {
+        Ok(Self::value_to_strings(&*self.item(section, key)?))
+    }
+
+    /// The TOML item stored under a key, looked up the same way as [`Config::list`]
+    pub fn item(&self, section: &str, key: &str) -> Result<Cow<'_, Item>> {
         let table = self.get_table(section)?;
-        let value = table.get(key)
-            .ok_or_else(|| ConfigError::KeyNotFound(key.to_string()))?;
-        Ok(Self::value_to_strings(value))
+        if let Some(item) = table.get(key) {
+            return Ok(Cow::Borrowed(item));
+        }
+        Self::collect_sub_keys(table, key)
+            .map(|collected| Cow::Owned(Item::Value(Value::InlineTable(collected))))
+            .ok_or_else(|| ConfigError::KeyNotFound(key.to_string()))
+    }
+
+    /// Rebuild the inline table that the flat `key.*` entries of a table came from,
+    /// e.g. `compiler.cc` and `compiler.cxx` into `{ cc = ..., cxx = ... }`
+    /// Returns `None` when no key starts with `key.`
+    fn collect_sub_keys(table: &Table, key: &str) -> Option<InlineTable> {
+        let prefix = format!("{}.", key);
+        let mut collected = InlineTable::new();
+        for (name, item) in table.iter() {
+            let (Some(rest), Some(value)) = (name.strip_prefix(&prefix), item.as_value()) else {
+                continue;
+            };
+            let mut value = value.clone();
+            value.decor_mut().clear();
+            let mut parts: Vec<&str> = rest.split('.').collect();
+            let leaf = parts.pop()?;
+            let mut current = &mut collected;
+            for part in parts {
+                let entry = current
+                    .entry(part)
+                    .or_insert_with(|| Value::InlineTable(InlineTable::new()));
+                current = entry.as_inline_table_mut()?;
+            }
+            current.insert(leaf, value);
+        }
+        (!collected.is_empty()).then_some(collected)
+    }
+
+    /// Replace the flat `key.*` entries that [`Config::collect_sub_keys`] reads back as `key`
+    /// with `replacement`, placed where the first of them was; `None` only removes them
+    fn replace_sub_keys(table: &mut Table, key: &str, mut replacement: Option<Item>) {
+        let prefix = format!("{}.", key);
+        let names: Vec<String> = table.iter().map(|(name, _)| name.to_string()).collect();
+        for name in names {
+            let Some((name_key, item)) = table.remove_entry(&name) else {
+                continue;
+            };
+            if !name.starts_with(&prefix) || !item.is_value() {
+                table.insert_formatted(&name_key, item);
+            } else if let Some(replacement) = replacement.take() {
+                // The comment above the first sub-key now belongs to the whole value
+                let mut new_key = Key::new(key);
+                if let Some(comment) = name_key.leaf_decor().prefix() {
+                    new_key.leaf_decor_mut().set_prefix(comment.clone());
+                }
+                table.insert_formatted(&new_key, replacement);
+            }
+        }
+    }
+
+    /// TOML text of a key's value as written in the file, e.g. `["a", "b"]` or `"gcc"`
+    /// Surrounding whitespace and comments are left out
+    pub fn raw(&self, section: &str, key: &str) -> Result<String> {
+        Ok(match &*self.item(section, key)? {
+            Item::Value(value) => {
+                let mut value = value.clone();
+                value.decor_mut().clear();
+                value.to_string()
+            }
+            item => item.to_string().trim().to_string(),
+        })
+    }
+
+    /// Typed value of a scalar key, or `None` if the key is not set
+    /// Fails if the key holds an array; use [`Config::get_array`] for those
+    pub fn get_scalar(&self, section: &str, key: &str) -> Result<Option<Scalar>> {
+        let table = self.get_table(section)?;
+        match table.get(key) {
+            None => Ok(None),
+            Some(Item::Value(Value::Array(_))) => Err(ConfigError::InvalidOperation(format!(
+                "'{}' holds an array, not a single value",
+                key
+            ))),
+            Some(Item::Value(value)) => Ok(Some(Scalar::from_value(value))),
+            Some(other) => Ok(Some(Scalar::Other(other.to_string().trim().to_string()))),
+        }
+    }
+
+    /// Typed elements of an array key, or `None` if the key is not set
+    /// Fails if the key holds a single value; use [`Config::get_scalar`] for those
+    pub fn get_array(&self, section: &str, key: &str) -> Result<Option<Vec<Scalar>>> {
+        let table = self.get_table(section)?;
+        match table.get(key) {
+            None => Ok(None),
+            Some(Item::Value(Value::Array(array))) => Ok(Some(array.iter().map(Scalar::from_value).collect())),
+            Some(_) => Err(ConfigError::InvalidOperation(format!(
+                "'{}' holds a single value, not an array",
+                key
+            ))),
+        }
+    }
+
+    /// Compare this config with `other`, treating `other` as the older state
+    /// Sections are visited in this config's order followed by sections only `other` has,
+    /// and keys in document order. A key counts as changed when its values or TOML type differ.
+    pub fn diff_against(&self, other: &Config) -> Vec<ConfigDiff> {
+        let mut sections = self.section_names();
+        sections.extend(other.section_names().into_iter().filter(|section| !self.section_exists(section)));
+
+        let entries = |config: &Config, section: &str| -> Vec<(String, &'static str, Vec<String>)> {
+            config.get_table(section)
+                .map(|table| {
+                    table.iter()
+                        .map(|(key, item)| (key.to_string(), item.type_name(), Self::value_to_strings(item)))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let mut diffs = Vec::new();
+        for section in sections {
+            let new_entries = entries(self, &section);
+            let old_entries = entries(other, &section);
+            let diff = |key: &str, kind, old: Option<&Vec<String>>, new: Option<&Vec<String>>| ConfigDiff {
+                section: section.clone(),
+                key: key.to_string(),
+                kind,
+                old: old.cloned(),
+                new: new.cloned(),
+            };
+
+            for (key, new_type, new_values) in &new_entries {
+                match old_entries.iter().find(|(old_key, _, _)| old_key == key) {
+                    None => diffs.push(diff(key, DiffKind::Added, None, Some(new_values))),
+                    Some((_, old_type, old_values)) if old_type != new_type || old_values != new_values => {
+                        diffs.push(diff(key, DiffKind::Changed, Some(old_values), Some(new_values)));
+                    }
+                    Some(_) => {}
+                }
+            }
+            for (key, _, old_values) in &old_entries {
+                if !new_entries.iter().any(|(new_key, _, _)| new_key == key) {
+                    diffs.push(diff(key, DiffKind::Removed, Some(old_values), None));
+                }
+            }
+        }
+        diffs
+    }
+
+    /// Pair each `build.files.N` group with its `build.options[N]` entry
+    /// Groups missing either side are still returned so callers can flag them as unpaired
+    pub fn build_groups(&self, section: &str) -> Result<Vec<BuildGroup>> {
+        let table = self.get_table(section)?;
+
+        let mut files: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for (key, value) in table {
+            if let Some(index) = key.strip_prefix("build.files.").and_then(|n| n.parse::<usize>().ok()) {
+                files.insert(index, Self::value_to_strings(value));
+            }
+        }
+
+        let options = table.get("build.options")
+            .map(Self::value_to_strings)
+            .unwrap_or_default();
+
+        let count = files.keys().next_back().map(|&max| max + 1).unwrap_or(0).max(options.len());
+        Ok((0..count)
+            .filter_map(|index| {
+                let group = BuildGroup {
+                    index,
+                    files: files.remove(&index),
+                    options: options.get(index).cloned(),
+                };
+                if group.files.is_none() && group.options.is_none() {
+                    None
+                } else {
+                    Some(group)
+                }
+            })
+            .collect())
+    }
+
+    /// Merge a document laid out like config.toml into the config
+    /// Every top-level table goes to the section of the same name (such as `global`, `schema`
+    /// or a table created with `--section`), except that every table under `feature` goes to
+    /// the matching `feature.<name>` section. Missing sections are created.
+    /// Nested tables are flattened into dotted keys first; `policy` decides what happens
+    /// to keys the config already has
+    pub fn merge_document(&mut self, other: &DocumentMut, policy: MergePolicy) -> Result<MergeReport> {
+        let mut sections = Vec::new();
+        for (name, item) in other.iter() {
+            let table = item
+                .as_table_like()
+                .ok_or_else(|| ConfigError::InvalidOperation(format!("'{}' must be a table", name)))?;
+            match name {
+                "feature" => {
+                    for (feature, item) in table.iter() {
+                        let feature_table = item.as_table_like().ok_or_else(|| {
+                            ConfigError::InvalidOperation(format!("'feature.{}' must be a table", feature))
+                        })?;
+                        sections.push((format!("feature.{}", feature), to_table(feature_table)));
+                    }
+                }
+                _ => sections.push((name.to_string(), to_table(table))),
+            }
+        }
+
+        let mut report = MergeReport::default();
+        for (section, mut source) in sections {
+            let _ = Self::flatten_table(&mut source);
+            let table = self.get_table_mut(&section, true)?;
+            for (key, item) in source.iter() {
+                let Some(existing) = table.get_mut(key) else {
+                    table.insert(key, item.clone());
+                    report.written += 1;
+                    continue;
+                };
+                match (existing.as_array(), item.as_array()) {
+                    _ if policy.missing_only => report.skipped.push(format!("{}.{}", section, key)),
+                    (Some(existing_array), Some(incoming)) if policy.concat_arrays => {
+                        let mut combined = incoming.clone();
+                        combined.extend(existing_array.iter().cloned());
+                        combined.fmt();
+                        Self::replace_value(existing, Value::Array(combined));
+                        report.written += 1;
+                    }
+                    _ if policy.overwrite => {
+                        match item.as_value() {
+                            Some(value) => Self::replace_value(existing, value.clone()),
+                            None => *existing = item.clone(),
+                        }
+                        report.written += 1;
+                    }
+                    _ => report.skipped.push(format!("{}.{}", section, key)),
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Replace a stored item with a new value, carrying over the old value's
+    /// decor so a trailing `# comment` on the line survives
+    fn replace_value(existing: &mut Item, mut value: Value) {
+        if let Some(old) = existing.as_value() {
+            *value.decor_mut() = old.decor().clone();
+        }
+        *existing = Item::Value(value);
+    }
+
+    /// Copy the keys of one feature into another, creating the target if needed
+    /// Keys the target already has are kept unless `overwrite` is set; with `append_arrays`,
+    /// arrays present on both sides become the base elements followed by the target's.
+    /// Returns how many keys of the target were added or changed; see [`Config::merge_document`]
+    pub fn merge_feature(&mut self, base: &str, target: &str, overwrite: bool, append_arrays: bool) -> Result<usize> {
+        let source = self.get_table(base)?.clone();
+        let mut document = DocumentMut::new();
+        match target.split_once('.') {
+            Some((parent, name)) => {
+                let mut parent_table = Table::new();
+                parent_table.insert(name, Item::Table(source));
+                document.insert(parent, Item::Table(parent_table));
+            }
+            None => {
+                document.insert(target, Item::Table(source));
+            }
+        }
+
+        let policy = MergePolicy { overwrite, concat_arrays: append_arrays, missing_only: false };
+        Ok(self.merge_document(&document, policy)?.written)
+    }
+
+    /// Rename a feature section, keeping its position and comments
+    /// If `to` already exists this fails unless `merge` is set, in which case the keys of `from`
+    /// are merged into `to` as [`Config::merge_feature`] does and `from` is removed.
+    /// Returns how many keys of `to` were added or changed by a merge (0 for a plain rename)
+    pub fn rename_feature(
+        &mut self,
+        from: &str,
+        to: &str,
+        merge: bool,
+        overwrite: bool,
+        append_arrays: bool,
+    ) -> Result<usize> {
+        let source = self.get_table(from)?.clone();
+        let to_name = to.strip_prefix("feature.").ok_or_else(|| {
+            ConfigError::InvalidOperation(format!("only feature sections can be renamed, not '{}'", to))
+        })?;
+        if from == to {
+            return Ok(0);
+        }
+
+        if self.section_exists(to) {
+            if !merge {
+                return Err(ConfigError::InvalidOperation(format!(
+                    "'{}' already exists; pass --merge to merge '{}' into it",
+                    to, from
+                )));
+            }
+            let merged = self.merge_feature(from, to, overwrite, append_arrays)?;
+            self.remove_feature(from)?;
+            self.nested.retain(|(section, _)| section != from);
+            return Ok(merged);
+        }
+
+        // The moved table keeps its position, so the renamed section stays where it was
+        self.remove_feature(from)?;
+        self.document["feature"]
+            .as_table_mut()
+            .ok_or_else(|| ConfigError::InvalidOperation("'feature' is not a table".to_string()))?
+            .insert(to_name, Item::Table(source));
+        self.nested = std::mem::take(&mut self.nested)
+            .into_iter()
+            .map(|(section, key)| if section == from { (to.to_string(), key) } else { (section, key) })
+            .collect();
+        Ok(0)
     }
 
     /// Set a key to one or more values
     pub fn set(&mut self, section: &str, key: &str, values: Vec<String>) -> Result<()> {
-        let table = self.get_table_mut(section, true)?;
+        self.set_typed(section, key, values, ValueType::String)
+    }
+
+    /// Set a key, storing each value as the given TOML type
+    pub fn set_typed(&mut self, section: &str, key: &str, values: Vec<String>, value_type: ValueType) -> Result<()> {
+        let parsed = values
+            .iter()
+            .map(|raw| value_type.parse(raw))
+            .collect::<Result<Vec<_>>>()?;
+        self.set_values(section, key, parsed)
+    }
 
+    /// Set a key to already-typed values: a single value is stored as a scalar,
+    /// several as an array
+    pub fn set_values(&mut self, section: &str, key: &str, mut values: Vec<Value>) -> Result<()> {
         let value = if values.len() == 1 {
-            toml::Value::String(values[0].clone())
+            values.remove(0)
         } else {
-            let array: Vec<toml::Value> = values.into_iter()
-                .map(toml::Value::String)
-                .collect();
-            toml::Value::Array(array)
+            Value::Array(values.into_iter().collect())
         };
+        self.set_value(section, key, value)
+    }
+
+    /// Set a key to an array of the given values, even when there is only one
+    pub fn set_array(&mut self, section: &str, key: &str, values: Vec<Value>) -> Result<()> {
+        self.set_value(section, key, Value::Array(values.into_iter().collect()))
+    }
+
+    /// Store a single TOML value under a key, creating the section if needed
+    fn set_value(&mut self, section: &str, key: &str, value: Value) -> Result<()> {
+        let table = self.get_table_mut(section, true)?;
 
-        table.insert(key.to_string(), value);
+        // Overwrite in place so the key keeps its position and leading comment
+        if let Some(existing) = table.get_mut(key) {
+            Self::replace_value(existing, value);
+        } else if Self::collect_sub_keys(table, key).is_some() {
+            // A value read back from its `key.*` sub-keys replaces all of them
+            Self::replace_sub_keys(table, key, Some(Item::Value(value)));
+        } else {
+            table.insert_formatted(&Key::new(key), Item::Value(value));
+        }
         Ok(())
     }
 
+    /// Insert `key = ""` with a trailing `# comment` as a placeholder to fill in later
+    /// Keys that are already set are left alone; returns whether the placeholder was inserted
+    pub fn insert_placeholder(&mut self, section: &str, key: &str, comment: &str) -> Result<bool> {
+        let table = self.get_table_mut(section, true)?;
+        if table.contains_key(key) {
+            return Ok(false);
+        }
+        let mut value = Value::from("");
+        value.decor_mut().set_suffix(format!(" # {}", comment));
+        table.insert_formatted(&Key::new(key), Item::Value(value));
+        Ok(true)
+    }
+
+    /// Write `comment` as `# ...` lines directly above a key, replacing any comment already there
+    /// Blank lines separating the key from the previous entry are kept
+    pub fn set_comment(&mut self, section: &str, key: &str, comment: &str) -> Result<()> {
+        let table = self.get_table_mut(section, false)?;
+        let mut key_mut = table.key_mut(key)
+            .ok_or_else(|| ConfigError::KeyNotFound(key.to_string()))?;
+        let decor = key_mut.leaf_decor_mut();
+
+        let existing = decor.prefix().and_then(|prefix| prefix.as_str()).unwrap_or("");
+        let comment_start = existing.find('#').unwrap_or(existing.len());
+        let blank_lines = &existing[..existing[..comment_start].rfind('\n').map_or(0, |i| i + 1)];
+
+        let mut prefix = blank_lines.to_string();
+        for line in comment.lines() {
+            prefix.push_str(&format!("# {}\n", line));
+        }
+        decor.set_prefix(prefix);
+        Ok(())
+    }
+
+    /// Sort the keys of a section alphabetically and give every entry the same spacing:
+    /// `key = value`, no blank lines between keys, comments above a key kept with it
+    /// Values, including arrays and trailing comments, are left as they are
+    pub fn canonicalize_section(&mut self, section: &str) -> Result<()> {
+        let table = self.get_table_mut(section, false)?;
+        table.sort_values();
+        for (mut key, item) in table.iter_mut() {
+            let decor = key.leaf_decor_mut();
+            let comments: String = decor.prefix()
+                .and_then(|prefix| prefix.as_str())
+                .unwrap_or("")
+                .lines()
+                .map(str::trim)
+                .filter(|line| line.starts_with('#'))
+                .map(|line| format!("{}\n", line))
+                .collect();
+            decor.set_prefix(comments);
+            decor.set_suffix(" ");
+
+            if let Some(value) = item.as_value_mut() {
+                let decor = value.decor_mut();
+                let trailing = decor.suffix().and_then(|suffix| suffix.as_str()).unwrap_or("").trim();
+                let suffix = if trailing.is_empty() { String::new() } else { format!(" {}", trailing) };
+                decor.set_prefix(" ");
+                decor.set_suffix(suffix);
+            }
+        }
+        Ok(())
+    }
+
+    /// Canonicalize every section, see [`Config::canonicalize_section`]
+    pub fn canonicalize(&mut self) -> Result<()> {
+        for section in self.section_names() {
+            self.canonicalize_section(&section)?;
+        }
+        Ok(())
+    }
+
+    /// Tidy the layout of the whole document without reordering anything:
+    /// one blank line between sections, no indentation or trailing whitespace,
+    /// `key = value` spacing, at most one blank line between keys and multi-line
+    /// arrays indented by four spaces. Comments are kept, and formatting twice
+    /// gives the same result.
+    pub fn format(&mut self) {
+        let sections = self.section_names();
+        let first = sections.iter()
+            .filter_map(|section| self.get_table(section).ok().and_then(Table::position))
+            .min();
+
+        for section in &sections {
+            let Ok(table) = self.get_table_mut(section, false) else {
+                continue;
+            };
+            let blank_line = if table.position() == first { "" } else { "\n" };
+            let decor = table.decor_mut();
+            let prefix = format!("{}{}", blank_line, comment_lines(decor.prefix(), ""));
+            let suffix = trailing_comment(decor.suffix());
+            decor.set_prefix(prefix);
+            decor.set_suffix(suffix);
+
+            for (index, (mut key, item)) in table.iter_mut().enumerate() {
+                let decor = key.leaf_decor_mut();
+                let raw = decor.prefix().and_then(|prefix| prefix.as_str()).unwrap_or("");
+                let had_blank_line = raw.split_inclusive('\n').any(|line| line.trim().is_empty());
+                let blank_line = if index > 0 && had_blank_line { "\n" } else { "" };
+                let prefix = format!("{}{}", blank_line, comment_lines(decor.prefix(), ""));
+                decor.set_prefix(prefix);
+                decor.set_suffix(" ");
+
+                if let Some(value) = item.as_value_mut() {
+                    if let Value::Array(array) = value {
+                        format_array(array);
+                    }
+                    let suffix = trailing_comment(value.decor().suffix());
+                    value.decor_mut().set_prefix(" ");
+                    value.decor_mut().set_suffix(suffix);
+                }
+            }
+        }
+
+        let trailing = comment_lines(Some(self.document.trailing()), "");
+        let trailing = if trailing.is_empty() { trailing } else { format!("\n{}", trailing) };
+        self.document.set_trailing(trailing);
+    }
+
     /// Unset (remove) a key
+    /// Comments attached to the key go with it, while the section header and its own
+    /// comments stay, even when the section is left empty
     pub fn unset(&mut self, section: &str, key: &str) -> Result<()> {
         let table = self.get_table_mut(section, false)?;
-        table.remove(key);
+        if table.remove(key).is_none() {
+            // A value read back from its `key.*` sub-keys goes with all of them
+            Self::replace_sub_keys(table, key, None);
+        }
+        if table.is_empty() {
+            table.set_implicit(false);
+        }
         Ok(())
     }
 
+    /// Remove `prefix` and every key under it (`prefix.*`) from a section
+    /// Returns how many keys were removed
+    pub fn unset_prefix(&mut self, section: &str, prefix: &str) -> Result<usize> {
+        let table = self.get_table_mut(section, false)?;
+        let nested = format!("{}.", prefix);
+        let before = table.len();
+        table.retain(|key, _| key != prefix && !key.starts_with(&nested));
+        if table.is_empty() {
+            table.set_implicit(false);
+        }
+        Ok(before - table.len())
+    }
+
+    /// Create a section if it does not exist yet
+    /// Returns whether the section was created
+    pub fn create_section(&mut self, section: &str) -> Result<bool> {
+        let existed = self.section_exists(section);
+        self.get_table_mut(section, true)?;
+        Ok(!existed)
+    }
+
+    /// Remove a whole `feature.<name>` section
+    /// `global` and `model` are not disposable and cannot be removed
+    pub fn remove_feature(&mut self, section: &str) -> Result<()> {
+        let feature_name = section.strip_prefix("feature.").ok_or_else(|| {
+            ConfigError::InvalidOperation(format!("only feature sections can be removed, not '{}'", section))
+        })?;
+        self.document
+            .get_mut("feature")
+            .and_then(Item::as_table_mut)
+            .and_then(|features| features.remove(feature_name))
+            .map(|_| ())
+            .ok_or_else(|| ConfigError::FeatureNotFound(section.to_string()))
+    }
+
     /// Add values to an array key
-    pub fn add(&mut self, section: &str, key: &str, values: Vec<String>) -> Result<()> {
+    /// Values are appended unless `position` is given, in which case they are
+    /// inserted in order starting at that index
+    /// Values already present are skipped; with `unique`, duplicates already stored in
+    /// the array are collapsed as well. Returns the number of duplicates skipped or removed
+    pub fn add(
+        &mut self,
+        section: &str,
+        key: &str,
+        values: Vec<String>,
+        position: Option<usize>,
+        unique: bool,
+    ) -> Result<usize> {
+        let values = values.into_iter().map(Value::from).collect();
+        self.add_values(section, key, values, position, unique)
+    }
+
+    /// Add already-typed values to an array key, as [`Config::add`] does for strings
+    /// An element counts as already present only if it has the same type and value
+    pub fn add_values(
+        &mut self,
+        section: &str,
+        key: &str,
+        values: Vec<Value>,
+        position: Option<usize>,
+        unique: bool,
+    ) -> Result<usize> {
         let table = self.get_table_mut(section, true)?;
 
-        let current = table.entry(key.to_string()).or_insert_with(|| toml::Value::Array(vec![]));
-        
-        // Convert string to array if needed
-        if current.is_str() {
-            let str_value = current.as_str().unwrap().to_string();
-            *current = toml::Value::Array(vec![toml::Value::String(str_value)]);
+        let current = table
+            .entry(key)
+            .or_insert_with(|| Item::Value(Value::Array(toml_edit::Array::new())));
+
+        // Convert a scalar (string, number, bool, ...) to a single-element array
+        if let Some(scalar) = current.as_value().filter(|value| !value.is_array()) {
+            let mut scalar = scalar.clone();
+            let decor = std::mem::take(scalar.decor_mut());
+            let mut array = Value::Array(std::iter::once(scalar).collect());
+            *array.decor_mut() = decor;
+            *current = Item::Value(array);
         }
-        
+
         let array = current.as_array_mut()
             .ok_or_else(|| ConfigError::InvalidOperation(format!("'{}' is not an array", key)))?;
 
+        let mut skipped = 0;
+        if unique {
+            let mut seen = std::collections::HashSet::new();
+            let before = array.len();
+            array.retain(|v| v.is_array() || v.is_inline_table() || seen.insert(Self::value_identity(v)));
+            skipped += before - array.len();
+        }
+
+        let mut index = match position {
+            Some(index) if index > array.len() => {
+                return Err(ConfigError::InvalidOperation(format!(
+                    "index {} is out of range for '{}' (length {})",
+                    index,
+                    key,
+                    array.len()
+                )));
+            }
+            Some(index) => index,
+            None => array.len(),
+        };
+
         // Add values with deduplication
         for value in values {
             // Check if value already exists in array
-            let exists = array.iter().any(|v| {
-                v.as_str().map(|s| s == value).unwrap_or(false)
-            });
-            
-            if !exists {
-                array.push(toml::Value::String(value));
+            let identity = Self::value_identity(&value);
+            let exists = array.iter().any(|v| Self::value_identity(v) == identity);
+
+            if exists {
+                skipped += 1;
+            } else {
+                array.insert(index, value);
+                index += 1;
             }
         }
 
-        Ok(())
+        Ok(skipped)
+    }
+
+    /// Type and rendered value of an array element, so `8080` and `"8080"` are distinct
+    fn value_identity(value: &Value) -> (&'static str, String) {
+        (value.type_name(), Self::value_to_string(value))
     }
 
     /// Delete values from an array key
-    pub fn del(&mut self, section: &str, key: &str, values: Vec<String>) -> Result<()> {
+    /// With an `index`, the element at that position is removed instead of matching `values`
+    /// Returns how many elements were removed
+    pub fn del(&mut self, section: &str, key: &str, values: Vec<String>, index: Option<usize>) -> Result<usize> {
+        let values = values.into_iter().map(Value::from).collect();
+        self.del_values(section, key, values, index)
+    }
+
+    /// Delete already-typed values from an array key, as [`Config::del`] does for strings
+    /// An element is removed only if it has the same type and value, as in [`Config::add_values`]
+    pub fn del_values(&mut self, section: &str, key: &str, values: Vec<Value>, index: Option<usize>) -> Result<usize> {
         let table = self.get_table_mut(section, false)?;
 
         let current = table.get_mut(key)
@@ -258,15 +1582,295 @@ impl Config {
 
         let array = current.as_array_mut()
             .ok_or_else(|| ConfigError::InvalidOperation(format!("'{}' is not an array", key)))?;
+        // The spacing before the first element, e.g. none in `["a", "b"]` or a newline in a multi-line array
+        let leading = array.get(0).map(|first| first.decor().prefix().cloned().unwrap_or_default());
 
-        // Use HashSet for O(n+m) performance instead of O(n*m)
-        let values_set: std::collections::HashSet<_> = values.iter().map(|s| s.as_str()).collect();
-        array.retain(|v| {
-            v.as_str()
-                .map(|s| !values_set.contains(s))
-                .unwrap_or(true)
-        });
+        let before = array.len();
+        if let Some(index) = index {
+            if index >= array.len() {
+                return Err(ConfigError::InvalidOperation(format!(
+                    "index {} is out of range for '{}' (length {})",
+                    index,
+                    key,
+                    array.len()
+                )));
+            }
+            array.remove(index);
+        } else {
+            // Use HashSet for O(n+m) performance instead of O(n*m)
+            let values_set: std::collections::HashSet<_> = values.iter().map(Self::value_identity).collect();
+            array.retain(|v| !values_set.contains(&Self::value_identity(v)));
+        }
 
-        Ok(())
+        // A later element moved to the front keeps the first element's spacing, not its own ` `
+        if let (Some(first), Some(leading)) = (array.get_mut(0), leading) {
+            first.decor_mut().set_prefix(leading);
+        }
+        Ok(before - array.len())
+    }
+
+    /// Replace every array element equal to `old` with `new`, in place
+    /// Elements are matched by their rendered value and keep their TOML type, so replacing
+    /// `8080` in an array of integers fails unless `new` is an integer too.
+    /// Returns how many elements were replaced
+    pub fn replace(&mut self, section: &str, key: &str, old: &str, new: &str) -> Result<usize> {
+        let table = self.get_table_mut(section, false)?;
+
+        let current = table.get_mut(key)
+            .ok_or_else(|| ConfigError::KeyNotFound(key.to_string()))?;
+
+        let array = current.as_array_mut()
+            .ok_or_else(|| ConfigError::InvalidOperation(format!("'{}' is not an array", key)))?;
+
+        let mut replaced = 0;
+        for value in array.iter_mut() {
+            if Self::value_to_string(value) == old {
+                let value_type = match value {
+                    Value::Integer(_) => ValueType::Int,
+                    Value::Boolean(_) => ValueType::Bool,
+                    Value::Float(_) => ValueType::Float,
+                    _ => ValueType::String,
+                };
+                let decor = value.decor().clone();
+                *value = value_type.parse(new)?;
+                *value.decor_mut() = decor;
+                replaced += 1;
+            }
+        }
+        Ok(replaced)
+    }
+
+    /// Replace `old` with `new` in every array of a section
+    /// Returns the keys that changed, in document order, with how many elements each had replaced
+    pub fn rename_value(&mut self, section: &str, old: &str, new: &str) -> Result<Vec<(String, usize)>> {
+        let arrays: Vec<String> = self.get_table(section)?
+            .iter()
+            .filter(|(_, item)| item.is_array())
+            .map(|(key, _)| key.to_string())
+            .collect();
+
+        let mut touched = Vec::new();
+        for key in arrays {
+            let replaced = self.replace(section, &key, old, new)?;
+            if replaced > 0 {
+                touched.push((key, replaced));
+            }
+        }
+        Ok(touched)
+    }
+}
+
+/// The comment lines of a decor prefix, trimmed and each indented by `indent`
+fn comment_lines(raw: Option<&toml_edit::RawString>, indent: &str) -> String {
+    raw.and_then(|raw| raw.as_str())
+        .unwrap_or("")
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with('#'))
+        .map(|line| format!("{}{}\n", indent, line))
+        .collect()
+}
+
+/// The comment at the end of a line, with a single space before it, or nothing
+fn trailing_comment(raw: Option<&toml_edit::RawString>) -> String {
+    match raw.and_then(|raw| raw.as_str()).unwrap_or("").trim() {
+        "" => String::new(),
+        comment => format!(" {}", comment),
+    }
+}
+
+/// Lay out an array on one line, or one element per line when it already spans several
+fn format_array(array: &mut toml_edit::Array) {
+    if !array.to_string().contains('\n') {
+        array.fmt();
+        return;
+    }
+    for element in array.iter_mut() {
+        let prefix = format!("\n{}    ", comment_lines(element.decor().prefix(), "    "));
+        element.decor_mut().set_prefix(prefix);
+        element.decor_mut().set_suffix("");
+    }
+    let trailing = format!("\n{}", comment_lines(Some(array.trailing()), "    "));
+    array.set_trailing(trailing);
+    array.set_trailing_comma(true);
+}
+
+/// Match `text` against a pattern where `*` stands for any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((head, tail)) => {
+            let Some(rest) = text.strip_prefix(head) else {
+                return false;
+            };
+            (0..=rest.len())
+                .filter(|&i| rest.is_char_boundary(i))
+                .any(|i| glob_match(tail, &rest[i..]))
+        }
+    }
+}
+
+/// Levenshtein distance between two strings, counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Turn a table-like item into an owned table
+fn to_table(table: &dyn TableLike) -> Table {
+    let mut result = Table::new();
+    for (key, item) in table.iter() {
+        result.insert(key, item.clone());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(edit_distance("debug", "debug"), 0);
+        assert_eq!(edit_distance("debug", "debg"), 1);
+        assert_eq!(edit_distance("debug", "debuf"), 1);
+        assert_eq!(edit_distance("debug", "debugs"), 1);
+        assert_eq!(edit_distance("debug", "release"), 6);
+    }
+
+    #[test]
+    fn glob_match_handles_wildcards() {
+        assert!(glob_match("build.*", "build.files.0"));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("build.files.*", "build.files.12"));
+        assert!(glob_match("compiler", "compiler"));
+        assert!(!glob_match("build.*", "clean.cmd"));
+        assert!(!glob_match("compiler", "compilers"));
+    }
+
+    #[test]
+    fn load_from_reads_values() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join(CONFIG_FILE);
+        fs::write(&config_path, "[global]\ncompiler = [\"gcc\", \"clang\"]\n\n[model]\n").unwrap();
+
+        let config = Config::load_from(&config_path).unwrap();
+        assert_eq!(config.list("global", "compiler").unwrap(), vec!["gcc", "clang"]);
+    }
+
+    #[test]
+    fn load_from_missing_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join(CONFIG_FILE);
+
+        assert!(matches!(
+            Config::load_from(&config_path),
+            Err(ConfigError::ConfigFileNotFound(path)) if path == config_path
+        ));
+    }
+
+    #[test]
+    fn load_from_invalid_toml() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join(CONFIG_FILE);
+        fs::write(&config_path, "[global\n").unwrap();
+
+        assert!(matches!(Config::load_from(&config_path), Err(ConfigError::TomlParseError(_))));
+    }
+
+    #[test]
+    fn set_then_list_in_memory() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join(CONFIG_FILE);
+        fs::write(&config_path, DEFAULT_CONTENT).unwrap();
+
+        let mut config = Config::load_from(&config_path).unwrap();
+        config.set("feature.default", "build.dir", vec!["build".to_string()]).unwrap();
+        assert_eq!(config.list("feature.default", "build.dir").unwrap(), vec!["build"]);
+    }
+
+    /// Merge a small document into a config holding `name = "old"` and `files = ["a"]`
+    fn merge_with(policy: MergePolicy) -> (Config, MergeReport) {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join(CONFIG_FILE);
+        fs::write(&config_path, "[global]\nname = \"old\"\nfiles = [\"a\"]\n\n[model]\n").unwrap();
+        let mut config = Config::load_from(&config_path).unwrap();
+
+        let other: DocumentMut = "[global]\nname = \"new\"\nfiles = [\"b\"]\nextra = \"x\"\n".parse().unwrap();
+        let report = config.merge_document(&other, policy).unwrap();
+        (config, report)
+    }
+
+    #[test]
+    fn merge_document_policies() {
+        let skipped = vec!["global.name".to_string(), "global.files".to_string()];
+        // (overwrite, concat_arrays, missing_only) -> name, files, written, skipped
+        let cases = [
+            ((false, false, false), "old", vec!["a"], 1, skipped.clone()),
+            ((true, false, false), "new", vec!["b"], 3, vec![]),
+            ((false, true, false), "old", vec!["b", "a"], 2, vec!["global.name".to_string()]),
+            ((true, true, false), "new", vec!["b", "a"], 3, vec![]),
+            ((true, true, true), "old", vec!["a"], 1, skipped.clone()),
+        ];
+        for ((overwrite, concat_arrays, missing_only), name, files, written, skipped) in cases {
+            let policy = MergePolicy { overwrite, concat_arrays, missing_only };
+            let (config, report) = merge_with(policy);
+            assert_eq!(config.list("global", "name").unwrap(), vec![name], "{:?}", policy);
+            assert_eq!(config.list("global", "files").unwrap(), files, "{:?}", policy);
+            assert_eq!(config.list("global", "extra").unwrap(), vec!["x"], "{:?}", policy);
+            assert_eq!(report, MergeReport { written, skipped }, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn export_then_import_round_trips_every_section() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_path = temp_dir.path().join(CONFIG_FILE);
+        fs::write(
+            &source_path,
+            "[global]\ncompiler = [\"gcc\"]\n\n[model]\n\n[feature.default]\nbuild.cmd = \"make\"\n\n\
+             [schema]\nfeature = [\"build.*\"]\nno-duplicates = [\"test.args\"]\n\n[cache]\ndir = \"/tmp\"\n",
+        )
+        .unwrap();
+        let source = Config::load_from(&source_path).unwrap();
+        let exported = crate::export::render(&crate::export::to_json(&source), crate::export::Format::Json).unwrap();
+
+        let target_path = temp_dir.path().join("target.toml");
+        fs::write(&target_path, DEFAULT_CONTENT).unwrap();
+        let mut target = Config::load_from(&target_path).unwrap();
+        let document = crate::import::parse(&exported, crate::import::Format::Json).unwrap();
+        target.merge_document(&document, MergePolicy::default()).unwrap();
+
+        for section in ["global", "feature.default", SCHEMA_SECTION, "cache"] {
+            assert_eq!(target.list_all(section).unwrap(), source.list_all(section).unwrap(), "{}", section);
+        }
+    }
+
+    #[test]
+    fn save_keeps_crlf_and_missing_final_newline() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join(CONFIG_FILE);
+        fs::write(&config_path, "[global]\r\ncompiler = \"gcc\"\r\n\r\n[model]\r\nname = \"m\"").unwrap();
+
+        let mut config = Config::load_from(&config_path).unwrap();
+        config.set("global", "compiler", vec!["clang".to_string()]).unwrap();
+        config.set("feature.default", "build.cmd", vec!["make".to_string()]).unwrap();
+        config.save().unwrap();
+
+        let saved = fs::read_to_string(&config_path).unwrap();
+        assert!(saved.contains("compiler = \"clang\"\r\n"), "{:?}", saved);
+        assert!(saved.contains("[feature.default]\r\nbuild.cmd = \"make\""), "{:?}", saved);
+        assert_eq!(saved.matches('\n').count(), saved.matches("\r\n").count(), "{:?}", saved);
+        assert!(!saved.ends_with('\n'), "{:?}", saved);
     }
 }
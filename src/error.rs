@@ -1,33 +1,89 @@
+use crate::i18n::{self, Lang};
 use std::fmt;
 use std::path::PathBuf;
 
+/// Errors reported by c2rust-config
+///
+/// Each category maps to a stable process exit code (see [`ConfigError::exit_code`]):
+///
+/// | Variant             | Exit code |
+/// |---------------------|-----------|
+/// | `IoError`           | 1         |
+/// | `KeyNotFound`       | 2         |
+/// | `FeatureNotFound`   | 3         |
+/// | `FeatureNotCreated` | 3         |
+/// | `ConfigDirNotFound` | 4         |
+/// | `ConfigFileNotFound`| 4         |
+/// | `InvalidOperation`  | 5         |
+/// | `TomlParseError`    | 6         |
+/// | `ValidationFailed`  | 7         |
 #[derive(Debug)]
 pub enum ConfigError {
-    ConfigDirNotFound(PathBuf),
+    /// No .c2rust directory between `start` and the filesystem root, or the
+    /// repository `boundary` where the search stopped
+    ConfigDirNotFound { start: PathBuf, boundary: Option<PathBuf> },
+    ConfigFileNotFound(PathBuf),
     FeatureNotFound(String),
+    /// A write to a feature that does not exist yet, without `--create`; its kind is `FeatureNotFound`
+    FeatureNotCreated(String),
     KeyNotFound(String),
     IoError(std::io::Error),
     TomlParseError(String),
     InvalidOperation(String),
+    ValidationFailed(usize),
+}
+
+impl ConfigError {
+    /// Process exit code for this error category, stable for scripting
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ConfigError::IoError(_) => 1,
+            ConfigError::KeyNotFound(_) => 2,
+            ConfigError::FeatureNotFound(_) | ConfigError::FeatureNotCreated(_) => 3,
+            ConfigError::ConfigDirNotFound { .. } | ConfigError::ConfigFileNotFound(_) => 4,
+            ConfigError::InvalidOperation(_) => 5,
+            ConfigError::TomlParseError(_) => 6,
+            ConfigError::ValidationFailed(_) => 7,
+        }
+    }
+
+    /// Stable, unlocalized name of the error category, e.g. `KeyNotFound`
+    pub fn kind_str(&self) -> &'static str {
+        match self {
+            ConfigError::ConfigDirNotFound { .. } => "ConfigDirNotFound",
+            ConfigError::ConfigFileNotFound(_) => "ConfigFileNotFound",
+            ConfigError::FeatureNotFound(_) | ConfigError::FeatureNotCreated(_) => "FeatureNotFound",
+            ConfigError::KeyNotFound(_) => "KeyNotFound",
+            ConfigError::IoError(_) => "IoError",
+            ConfigError::TomlParseError(_) => "TomlParseError",
+            ConfigError::InvalidOperation(_) => "InvalidOperation",
+            ConfigError::ValidationFailed(_) => "ValidationFailed",
+        }
+    }
 }
 
 impl fmt::Display for ConfigError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ConfigError::ConfigDirNotFound(path) => {
-                // Multi-line error message for better readability in CLI output
-                write!(f, "错误：未能找到 .c2rust 目录。\n搜索起始路径：{}\n已向上遍历至根目录但未找到项目根目录。\n请在项目根目录创建 .c2rust 目录。", path.display())
-            }
-            ConfigError::FeatureNotFound(feature) => {
-                write!(f, "Error: feature '{}' not found in configuration", feature)
+        let messages = Lang::current().messages();
+        let message = match self {
+            // Multi-line error message for better readability in CLI output
+            ConfigError::ConfigDirNotFound { start, boundary: None } => {
+                i18n::format(messages.config_dir_not_found, start.display())
             }
-            ConfigError::KeyNotFound(key) => {
-                write!(f, "Error: key '{}' not found", key)
-            }
-            ConfigError::IoError(e) => write!(f, "IO error: {}", e),
-            ConfigError::TomlParseError(e) => write!(f, "TOML parse error: {}", e),
-            ConfigError::InvalidOperation(msg) => write!(f, "Invalid operation: {}", msg),
-        }
+            ConfigError::ConfigDirNotFound { start, boundary: Some(boundary) } => i18n::format(
+                &i18n::format(messages.config_dir_boundary, start.display()),
+                boundary.display(),
+            ),
+            ConfigError::ConfigFileNotFound(path) => i18n::format(messages.config_file_not_found, path.display()),
+            ConfigError::FeatureNotFound(feature) => i18n::format(messages.feature_not_found, feature),
+            ConfigError::FeatureNotCreated(feature) => i18n::format(messages.feature_not_created, feature),
+            ConfigError::KeyNotFound(key) => i18n::format(messages.key_not_found, key),
+            ConfigError::IoError(e) => i18n::format(messages.io_error, e),
+            ConfigError::TomlParseError(e) => i18n::format(messages.toml_parse_error, e),
+            ConfigError::InvalidOperation(msg) => i18n::format(messages.invalid_operation, msg),
+            ConfigError::ValidationFailed(count) => i18n::format(messages.validation_failed, count),
+        };
+        f.write_str(&message)
     }
 }
 
@@ -39,14 +95,8 @@ impl From<std::io::Error> for ConfigError {
     }
 }
 
-impl From<toml::de::Error> for ConfigError {
-    fn from(err: toml::de::Error) -> Self {
-        ConfigError::TomlParseError(err.to_string())
-    }
-}
-
-impl From<toml::ser::Error> for ConfigError {
-    fn from(err: toml::ser::Error) -> Self {
+impl From<toml_edit::TomlError> for ConfigError {
+    fn from(err: toml_edit::TomlError) -> Self {
         ConfigError::TomlParseError(err.to_string())
     }
 }
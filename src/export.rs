@@ -0,0 +1,134 @@
+use crate::config::Config;
+use crate::error::{ConfigError, Result};
+use serde_json::{Map, Number, Value as JsonValue};
+use toml_edit::{Item, Table, Value};
+
+/// Output format for exported configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+/// Convert a TOML value into JSON, preserving scalar types
+/// Datetimes and non-finite floats have no JSON equivalent and are emitted as strings
+fn value_to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::String(s) => JsonValue::String(s.value().clone()),
+        Value::Integer(i) => JsonValue::from(*i.value()),
+        Value::Float(f) => Number::from_f64(*f.value())
+            .map(JsonValue::Number)
+            .unwrap_or_else(|| JsonValue::String(f.value().to_string())),
+        Value::Boolean(b) => JsonValue::Bool(*b.value()),
+        Value::Datetime(dt) => JsonValue::String(dt.value().to_string()),
+        Value::Array(array) => JsonValue::Array(array.iter().map(value_to_json).collect()),
+        Value::InlineTable(table) => JsonValue::Object(
+            table.iter().map(|(key, value)| (key.to_string(), value_to_json(value))).collect(),
+        ),
+    }
+}
+
+/// Convert a TOML item into JSON
+fn item_to_json(item: &Item) -> JsonValue {
+    match item {
+        Item::None => JsonValue::Null,
+        Item::Value(value) => value_to_json(value),
+        Item::Table(table) => JsonValue::Object(table_to_json(table, false)),
+        Item::ArrayOfTables(tables) => JsonValue::Array(
+            tables.iter().map(|table| JsonValue::Object(table_to_json(table, false))).collect(),
+        ),
+    }
+}
+
+/// Insert a value at a dotted key path, creating intermediate objects
+/// If a path segment is already taken by a non-object value, the key is kept literally
+fn insert_dotted(object: &mut Map<String, JsonValue>, key: &str, value: JsonValue) {
+    let parts: Vec<&str> = key.split('.').collect();
+    let mut current = &mut *object;
+    for part in &parts[..parts.len() - 1] {
+        let entry = current
+            .entry(part.to_string())
+            .or_insert_with(|| JsonValue::Object(Map::new()));
+        match entry {
+            JsonValue::Object(next) => current = next,
+            _ => {
+                object.insert(key.to_string(), value);
+                return;
+            }
+        }
+    }
+    current.insert(parts[parts.len() - 1].to_string(), value);
+}
+
+/// Convert a TOML table into a JSON object, optionally expanding dotted keys
+fn table_to_json(table: &Table, expand_dotted: bool) -> Map<String, JsonValue> {
+    let mut object = Map::new();
+    for (key, item) in table.iter() {
+        let value = item_to_json(item);
+        if expand_dotted {
+            insert_dotted(&mut object, key, value);
+        } else {
+            object.insert(key.to_string(), value);
+        }
+    }
+    object
+}
+
+/// Build a JSON tree of the whole configuration
+/// Dotted keys inside `global`, `model` and each feature are expanded into nested objects
+pub fn to_json(config: &Config) -> JsonValue {
+    let mut root = Map::new();
+    for (name, item) in config.document().iter() {
+        let value = match (name, item.as_table()) {
+            ("feature", Some(features)) => JsonValue::Object(
+                features.iter()
+                    .map(|(feature, item)| {
+                        let value = match item.as_table() {
+                            Some(table) => JsonValue::Object(table_to_json(table, true)),
+                            None => item_to_json(item),
+                        };
+                        (feature.to_string(), value)
+                    })
+                    .collect(),
+            ),
+            (_, Some(table)) => JsonValue::Object(table_to_json(table, true)),
+            (_, None) => item_to_json(item),
+        };
+        root.insert(name.to_string(), value);
+    }
+    JsonValue::Object(root)
+}
+
+/// Convert the value of one key into JSON, keeping the structure of arrays and tables
+pub fn key_to_json(config: &Config, section: &str, key: &str) -> Result<JsonValue> {
+    Ok(item_to_json(&*config.item(section, key)?))
+}
+
+/// Build a JSON array with one object per feature, in document order
+/// Each object carries the feature `name` next to its expanded keys (`build`, `clean`, `test`, ...)
+pub fn features_to_json(config: &Config) -> JsonValue {
+    let features = config.document().get("feature").and_then(Item::as_table);
+    JsonValue::Array(
+        features
+            .into_iter()
+            .flat_map(|features| features.iter())
+            .filter_map(|(name, item)| {
+                let mut object = Map::new();
+                object.insert("name".to_string(), JsonValue::String(name.to_string()));
+                object.extend(table_to_json(item.as_table()?, true));
+                Some(JsonValue::Object(object))
+            })
+            .collect(),
+    )
+}
+
+/// Render a JSON tree in the requested format
+pub fn render(value: &JsonValue, format: Format) -> Result<String> {
+    match format {
+        Format::Json => serde_json::to_string_pretty(value)
+            .map(|s| s + "\n")
+            .map_err(|e| ConfigError::InvalidOperation(format!("failed to render JSON: {}", e))),
+        Format::Yaml => serde_yaml::to_string(value)
+            .map_err(|e| ConfigError::InvalidOperation(format!("failed to render YAML: {}", e))),
+    }
+}
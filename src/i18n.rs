@@ -0,0 +1,75 @@
+use std::env;
+
+/// Language used for user-facing messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    Zh,
+}
+
+impl Lang {
+    /// Resolve the language from the `C2RUST_LANG` environment variable
+    /// Accepts `en` and `zh` (case-insensitive, region suffixes like `zh_CN` are ignored);
+    /// anything else falls back to English
+    pub fn current() -> Self {
+        match env::var("C2RUST_LANG") {
+            Ok(value) if value.to_lowercase().starts_with("zh") => Lang::Zh,
+            _ => Lang::En,
+        }
+    }
+
+    /// Message table for this language
+    pub fn messages(self) -> &'static Messages {
+        match self {
+            Lang::En => &EN,
+            Lang::Zh => &ZH,
+        }
+    }
+}
+
+/// Message templates for every error category
+/// Each template contains a single `{}` placeholder for the error detail,
+/// except `config_dir_boundary` which takes the search start and then the boundary
+pub struct Messages {
+    pub config_dir_not_found: &'static str,
+    pub config_dir_boundary: &'static str,
+    pub config_file_not_found: &'static str,
+    pub feature_not_found: &'static str,
+    pub feature_not_created: &'static str,
+    pub key_not_found: &'static str,
+    pub io_error: &'static str,
+    pub toml_parse_error: &'static str,
+    pub invalid_operation: &'static str,
+    pub validation_failed: &'static str,
+}
+
+const EN: Messages = Messages {
+    config_dir_not_found: "Error: could not find a .c2rust directory.\nSearch started at: {}\nTraversed up to the filesystem root without finding the project root.\nPlease create a .c2rust directory in the project root.",
+    config_dir_boundary: "Error: could not find a .c2rust directory.\nSearch started at: {}\nStopped at the repository boundary: {}\nPlease create a .c2rust directory in the project root.",
+    config_file_not_found: "Error: config file not found: {}",
+    feature_not_found: "Error: feature '{}' not found in configuration",
+    feature_not_created: "Error: feature '{}' does not exist yet; pass --create to create it",
+    key_not_found: "Error: key '{}' not found",
+    io_error: "IO error: {}",
+    toml_parse_error: "TOML parse error: {}",
+    invalid_operation: "Invalid operation: {}",
+    validation_failed: "Error: validation found {} warning(s)",
+};
+
+const ZH: Messages = Messages {
+    config_dir_not_found: "错误：未能找到 .c2rust 目录。\n搜索起始路径：{}\n已向上遍历至根目录但未找到项目根目录。\n请在项目根目录创建 .c2rust 目录。",
+    config_dir_boundary: "错误：未能找到 .c2rust 目录。\n搜索起始路径：{}\n已在仓库边界处停止搜索：{}\n请在项目根目录创建 .c2rust 目录。",
+    config_file_not_found: "错误：未找到配置文件：{}",
+    feature_not_found: "错误：配置中未找到特性 '{}'",
+    feature_not_created: "错误：特性 '{}' 尚不存在；请传入 --create 以创建它",
+    key_not_found: "错误：未找到键 '{}'",
+    io_error: "IO 错误：{}",
+    toml_parse_error: "TOML 解析错误：{}",
+    invalid_operation: "无效操作：{}",
+    validation_failed: "错误：校验发现 {} 个警告",
+};
+
+/// Fill the `{}` placeholder of a message template
+pub fn format(template: &str, detail: impl std::fmt::Display) -> String {
+    template.replacen("{}", &detail.to_string(), 1)
+}
@@ -0,0 +1,87 @@
+use crate::config::{Config, MergePolicy};
+use crate::error::{ConfigError, Result};
+use serde_json::Value as JsonValue;
+use std::path::Path;
+use toml_edit::{DocumentMut, InlineTable, Value};
+
+/// Input format for imported configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// Guess the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// Summary of an import
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Number of keys written into the config
+    pub imported: usize,
+    /// `section.key` entries left untouched because they already existed
+    pub skipped: Vec<String>,
+}
+
+/// Convert a JSON value into a TOML value
+/// TOML has no null, so null values are rejected
+fn json_to_value(value: &JsonValue) -> Result<Value> {
+    Ok(match value {
+        JsonValue::Null => {
+            return Err(ConfigError::InvalidOperation("null values have no TOML equivalent".to_string()));
+        }
+        JsonValue::Bool(b) => Value::from(*b),
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(i) => Value::from(i),
+            None => Value::from(n.as_f64().unwrap_or_default()),
+        },
+        JsonValue::String(s) => Value::from(s.as_str()),
+        JsonValue::Array(items) => Value::Array(items.iter().map(json_to_value).collect::<Result<_>>()?),
+        JsonValue::Object(object) => {
+            let mut table = InlineTable::new();
+            for (key, value) in object {
+                table.insert(key, json_to_value(value)?);
+            }
+            Value::InlineTable(table)
+        }
+    })
+}
+
+/// Parse a single JSON value (scalar, array or object) into a TOML value
+/// Objects become inline tables
+pub fn parse_json_value(text: &str) -> Result<Value> {
+    let json: JsonValue = serde_json::from_str(text)
+        .map_err(|e| ConfigError::InvalidOperation(format!("invalid JSON: {}", e)))?;
+    json_to_value(&json)
+}
+
+/// Parse external content into a TOML document
+pub fn parse(content: &str, format: Format) -> Result<DocumentMut> {
+    match format {
+        Format::Toml => Ok(content.parse()?),
+        Format::Json => {
+            let json: JsonValue = serde_json::from_str(content)
+                .map_err(|e| ConfigError::InvalidOperation(format!("invalid JSON: {}", e)))?;
+            match json_to_value(&json)? {
+                Value::InlineTable(table) => Ok(DocumentMut::from(table.into_table())),
+                _ => Err(ConfigError::InvalidOperation("JSON input must be an object".to_string())),
+            }
+        }
+    }
+}
+
+/// Merge an imported document into the config
+/// Every top-level table goes to the section of the same name, and every table under
+/// `feature` goes to the matching `feature.<name>` section (see [`Config::merge_document`])
+pub fn import(config: &mut Config, document: &DocumentMut, overwrite: bool) -> Result<ImportReport> {
+    let report = config.merge_document(document, MergePolicy { overwrite, ..MergePolicy::default() })?;
+    Ok(ImportReport { imported: report.written, skipped: report.skipped })
+}
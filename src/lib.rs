@@ -0,0 +1,30 @@
+//! Configuration management for c2rust translation work.
+//!
+//! The configuration lives in `.c2rust/config.toml` and is split into a `[global]`
+//! section, a `[model]` section and one `[feature.<name>]` section per build feature.
+//!
+//! ```no_run
+//! use c2rust_config::Config;
+//! use std::path::Path;
+//!
+//! let mut config = Config::load_from(Path::new(".c2rust/config.toml"))?;
+//! config.set("feature.default", "build.cmd", vec!["make".to_string()])?;
+//! config.save()?;
+//! # Ok::<(), c2rust_config::ConfigError>(())
+//! ```
+
+pub mod config;
+pub mod error;
+pub mod export;
+mod i18n;
+pub mod import;
+pub mod operations;
+pub mod style;
+pub mod transaction;
+pub mod validate;
+#[cfg(feature = "watch")]
+pub mod watch;
+
+pub use config::{BuildGroup, Config, ConfigDiff, DiffKind, Entry, EntryValue, MergePolicy, MergeReport, Scalar, ValueType};
+pub use error::{ConfigError, Result};
+pub use operations::{execute, Operation, Options};
@@ -1,64 +1,1201 @@
-mod config;
-mod error;
-mod operations;
-
-use clap::{Args, Parser, Subcommand};
-use config::Config;
-use error::ConfigError;
-use operations::Operation;
+use c2rust_config::export::{self, Format};
+use c2rust_config::import;
+use c2rust_config::{operations, style, transaction, validate, Config, ConfigError, DiffKind, Operation, Options, ValueType};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "c2rust-config")]
 #[command(about = "Configuration management tool for c2rust translation work")]
 struct Cli {
+    /// How to report errors on stderr
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    error_format: ErrorFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Error output style selected by `--error-format`
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ErrorFormat {
+    /// The localized message
+    Text,
+    /// A JSON object with `kind`, `message` and `code` fields
+    Json,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Configuration management
-    Config(ConfigArgs),
+    Config(Box<ConfigArgs>),
+
+    /// Check every feature section and exit nonzero if any warning is found
+    Validate {
+        /// Also treat required keys with empty values as missing
+        #[arg(long)]
+        strict: bool,
+    },
+
+    /// Run every check on the whole config and print a report grouped by category
+    Doctor {
+        /// Also treat required keys with empty values as missing
+        #[arg(long)]
+        strict: bool,
+
+        /// Apply safe repairs (dotted keys instead of nested tables, duplicate removal) before reporting
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Dump the whole configuration as JSON or YAML
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value = "json")]
+        format: Format,
+
+        /// Write to this file instead of stdout, creating parent directories
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Only export the features, as an array of objects with a `name` field
+        #[arg(long)]
+        features_only: bool,
+
+        /// Export an empty config instead of failing when the .c2rust directory or config.toml is missing
+        #[arg(long)]
+        lenient: bool,
+    },
+
+    /// Merge keys from an external JSON or TOML file into the config
+    Import {
+        /// File to import
+        path: PathBuf,
+
+        /// Input format (inferred from the file extension when omitted)
+        #[arg(long, value_enum)]
+        format: Option<import::Format>,
+
+        /// Replace keys that already exist instead of skipping them
+        #[arg(long)]
+        overwrite: bool,
+    },
+
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// List the names of all feature sections
+    ListFeatures,
+
+    /// Copy the keys of one feature into another that lacks them
+    MergeFeature {
+        /// Feature to copy keys from
+        base: String,
+
+        /// Feature to merge into (created if it does not exist)
+        into: String,
+
+        /// Replace keys the target feature already has
+        #[arg(long)]
+        overwrite: bool,
+
+        /// Concatenate arrays present in both features (base elements first)
+        #[arg(long)]
+        append_arrays: bool,
+    },
+
+    /// Rename a feature section, keeping its keys, comments and position
+    RenameFeature {
+        /// Feature to rename
+        from: String,
+
+        /// New name of the feature
+        to: String,
+
+        /// If the new name is taken, merge into that feature instead of failing
+        #[arg(long)]
+        merge: bool,
+
+        /// With --merge, replace keys the existing feature already has
+        #[arg(long, requires = "merge")]
+        overwrite: bool,
+
+        /// With --merge, concatenate arrays present in both features (renamed feature's elements first)
+        #[arg(long, requires = "merge")]
+        append_arrays: bool,
+    },
+
+    /// Show what changed in config.toml compared with a backup or another config file
+    Diff {
+        /// Config file to compare against
+        #[arg(required_unless_present = "backup", conflicts_with = "backup")]
+        path: Option<PathBuf>,
+
+        /// Compare against the most recent config.toml.bak in the .c2rust directory
+        #[arg(long)]
+        backup: bool,
+
+        /// Write to this file instead of stdout, creating parent directories
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+
+    /// Rewrite config.toml with consistent spacing and blank lines, keeping comments and key order
+    Format,
+
+    /// Describe the sections and keys c2rust-config expects, one line per key
+    PrintConfigSchema {
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: SchemaFormat,
+    },
+
+    /// Print completion candidates taken from the current config.toml
+    #[command(name = "__complete", hide = true)]
+    Complete(CompleteArgs),
+
+    /// Apply config commands read from stdin, one per line, and save once
+    Batch {
+        /// Skip lines that fail instead of aborting the whole batch
+        #[arg(long)]
+        continue_on_error: bool,
+    },
+
+    /// Stage several config changes and apply them all-or-nothing
+    Tx {
+        #[command(subcommand)]
+        action: TxAction,
+    },
+}
+
+/// Output style of `print-config-schema`
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SchemaFormat {
+    /// One `[section]` heading per section followed by indented key descriptions
+    Text,
+    /// An object mapping each section to its description and keys
+    Json,
+}
+
+#[derive(Subcommand)]
+enum TxAction {
+    /// Start a transaction; config operations then act on .c2rust/config.toml.staging
+    Begin,
+    /// Replace config.toml with the staged changes
+    Commit,
+    /// Discard the staged changes
+    Abort,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum CompleteTarget {
+    /// Keys of the selected section
+    Keys,
+    /// Names of the existing features
+    Features,
+}
+
+#[derive(Args)]
+struct CompleteArgs {
+    /// What to complete
+    #[arg(value_enum)]
+    target: CompleteTarget,
+
+    /// Complete keys of the global section
+    #[arg(long, group = "mode")]
+    global: bool,
+
+    /// Complete keys of the model section
+    #[arg(long, group = "mode")]
+    model: bool,
+
+    /// Complete keys of a feature section (the default)
+    #[arg(long, group = "mode")]
+    make: bool,
+
+    /// Feature whose keys to complete
+    #[arg(long)]
+    feature: Option<String>,
+}
+
+/// Drop one trailing line ending (`\n` or `\r\n`) from file or stdin content
+fn strip_newline(content: &str) -> &str {
+    content
+        .strip_suffix('\n')
+        .map(|content| content.strip_suffix('\r').unwrap_or(content))
+        .unwrap_or(content)
+}
+
+/// Sections a `config` command acts on
+/// The default feature and `--feature all` stay symbolic until the invocation is applied, so
+/// they are looked up in the config being changed, including the effect of earlier batch lines
+enum Target {
+    /// Sections named on the command line; several when several features were given to `--feature`
+    Sections(Vec<String>),
+    /// `--make` without `--feature`: `[global] default_feature`, or `default` when that is not set
+    DefaultFeature,
+    /// `--feature all` (or `*`): every feature; features missing the key are then skipped
+    AllFeatures,
+}
+
+impl Target {
+    /// The sections this target stands for in `config`
+    fn sections(&self, config: &Config) -> Result<Vec<String>, ConfigError> {
+        match self {
+            Target::Sections(sections) => Ok(sections.clone()),
+            Target::DefaultFeature => Ok(vec![Config::feature_section(config.default_feature())?]),
+            Target::AllFeatures => {
+                Ok(config.feature_names().into_iter().map(|name| format!("feature.{}", name)).collect())
+            }
+        }
+    }
+}
+
+/// A `config` command line resolved into what to run against which sections
+struct Invocation {
+    operation: Operation,
+    target: Target,
+    key: String,
+    values: Vec<String>,
+    /// Keys and values given as `key=value` pairs with `--kv`, used instead of `key` and `values`
+    pairs: Vec<(String, Vec<String>)>,
+    options: Options,
+    /// How the section was selected (`global`, `model`, `make` or `section`), for `--log`
+    mode: &'static str,
+    /// Treat a missing config as empty instead of failing (read operations only)
+    lenient: bool,
+    /// File that successful mutations are appended to
+    log: Option<PathBuf>,
+    /// File that listings, or the mutated config, are written to instead of stdout or config.toml
+    output: Option<PathBuf>,
+}
+
+impl Invocation {
+    /// Whether `--feature all` selected every feature
+    fn all_features(&self) -> bool {
+        matches!(self.target, Target::AllFeatures)
+    }
+
+    /// Whether the operation only prints a listing
+    fn lists(&self) -> bool {
+        matches!(self.operation, Operation::List | Operation::ListBuilds | Operation::Keys)
+    }
+
+    /// Keys to act on with their values: the `--kv` pairs, or else the single key
+    fn targets(&self) -> Vec<(&str, &[String])> {
+        if self.pairs.is_empty() {
+            vec![(self.key.as_str(), self.values.as_slice())]
+        } else {
+            self.pairs.iter().map(|(key, values)| (key.as_str(), values.as_slice())).collect()
+        }
+    }
+}
+
+/// Validate the flags of a `config` command and resolve its section, operation and key
+fn resolve(mut args: ConfigArgs) -> Result<Invocation, ConfigError> {
+    // Validate exactly one mode is selected (--list --recursive covers every section instead)
+    let mode_count = [args.global, args.model, args.make, args.section.is_some()].iter().filter(|&&x| x).count();
+    if args.recursive {
+        if mode_count != 0 || args.key.is_some() {
+            return Err(ConfigError::InvalidOperation(
+                "--recursive lists every section and takes no mode or key".to_string(),
+            ));
+        }
+    } else if mode_count != 1 {
+        return Err(ConfigError::InvalidOperation(
+            "Exactly one of --global, --model, --make, or --section must be specified".to_string(),
+        ));
+    }
+
+    // Validate exactly one operation is selected
+    if args.operation_count() != 1 {
+        let (last, rest) = OPERATION_FLAGS.split_last().expect("operation flags");
+        return Err(ConfigError::InvalidOperation(format!(
+            "Exactly one of {}, or {} must be specified",
+            rest.join(", "),
+            last
+        )));
+    }
+
+    // Validate --feature is only used with --make
+    if !args.feature.is_empty() && !args.make {
+        return Err(ConfigError::InvalidOperation(
+            "--feature can only be used with --make".to_string(),
+        ));
+    }
+
+    let mode = if args.section.is_some() {
+        "section"
+    } else if args.global {
+        "global"
+    } else if args.model {
+        "model"
+    } else {
+        "make"
+    };
+
+    // Determine the sections
+    let all_features = args.feature.iter().any(|name| name.eq_ignore_ascii_case("all") || name == "*");
+    let target = if let Some(section) = args.section.take() {
+        // `[feature]` only holds the feature tables; writing leaf keys into it would break every feature
+        if section == "feature" {
+            return Err(ConfigError::InvalidOperation(
+                "'feature' holds the feature sections and cannot be used with --section; use --make --feature <name>"
+                    .to_string(),
+            ));
+        }
+        Target::Sections(vec![Config::section_name(&section)?])
+    } else if args.global {
+        Target::Sections(vec!["global".to_string()])
+    } else if args.model {
+        Target::Sections(vec!["model".to_string()])
+    } else if all_features {
+        Target::AllFeatures
+    } else if args.feature.is_empty() {
+        Target::DefaultFeature
+    } else {
+        Target::Sections(args.feature.iter().map(|name| Config::feature_section(name)).collect::<Result<_, _>>()?)
+    };
+
+    // Determine the operation
+    let operation = if args.set {
+        Operation::Set
+    } else if args.unset {
+        Operation::Unset
+    } else if args.add {
+        Operation::Add
+    } else if args.del {
+        Operation::Del
+    } else if args.replace {
+        Operation::Replace
+    } else if args.rename_value {
+        Operation::RenameValue
+    } else if args.canonical {
+        Operation::Canonical
+    } else if args.ensure {
+        Operation::Ensure
+    } else if args.exists {
+        Operation::Exists
+    } else if args.list {
+        Operation::List
+    } else if args.keys {
+        Operation::Keys
+    } else {
+        Operation::ListBuilds
+    };
+
+    // --rename-value works on the whole section, so its first positional is OLD rather than a key
+    if matches!(operation, Operation::RenameValue) {
+        if let Some(old) = args.key.take() {
+            args.values.insert(0, old);
+        }
+    }
+
+    // Validate operation-specific requirements
+    let key = match operation {
+        Operation::List | Operation::Exists | Operation::RenameValue => args.key.unwrap_or_default(),
+        // Without a key, --make --unset removes the whole feature section (one feature at a time)
+        Operation::Unset if args.make && !args.prefix && !all_features => args.key.unwrap_or_default(),
+        Operation::ListBuilds | Operation::Keys | Operation::Ensure | Operation::Canonical => match args.key {
+            Some(_) => {
+                return Err(ConfigError::InvalidOperation(format!(
+                    "{} does not take a key",
+                    operation_flag(operation)
+                )));
+            }
+            None => String::new(),
+        },
+        _ => args.key.ok_or_else(|| {
+            ConfigError::InvalidOperation(format!("{} requires a key", operation_flag(operation)))
+        })?,
+    };
+
+    if args.index.is_some() && !args.values.is_empty() {
+        return Err(ConfigError::InvalidOperation(
+            "--del --index does not take values".to_string(),
+        ));
+    }
+
+    let mut values = args.values;
+
+    // With --kv the key and values are key=value pairs, all set in the same load/save cycle
+    let mut pairs = Vec::new();
+    let key = if args.kv {
+        for pair in std::iter::once(key).chain(values.drain(..)) {
+            let (pair_key, value) = pair
+                .split_once('=')
+                .filter(|(pair_key, _)| !pair_key.is_empty())
+                .ok_or_else(|| ConfigError::InvalidOperation(format!("--kv expects key=value pairs, got '{}'", pair)))?;
+            pairs.push((pair_key.to_string(), vec![value.to_string()]));
+        }
+        String::new()
+    } else {
+        key
+    };
+
+    if let Some(path) = &args.from_file {
+        if !values.is_empty() {
+            return Err(ConfigError::InvalidOperation(
+                "--from-file cannot be combined with positional values".to_string(),
+            ));
+        }
+        values.push(strip_newline(&fs::read_to_string(path)?).to_string());
+    }
+
+    if args.stdin {
+        if !matches!(operation, Operation::Set | Operation::Add) {
+            return Err(ConfigError::InvalidOperation("--stdin only applies to --set and --add".to_string()));
+        }
+        if !values.is_empty() {
+            return Err(ConfigError::InvalidOperation(
+                "--stdin cannot be combined with positional values".to_string(),
+            ));
+        }
+        let input = std::io::read_to_string(std::io::stdin())?;
+        if matches!(operation, Operation::Add) {
+            values.extend(input.lines().filter(|line| !line.is_empty()).map(str::to_string));
+        } else if !input.is_empty() {
+            values.push(strip_newline(&input).to_string());
+        }
+    }
+
+    if let Some(separator) = &args.split {
+        if !matches!(operation, Operation::Add | Operation::Del) {
+            return Err(ConfigError::InvalidOperation("--split only applies to --add and --del".to_string()));
+        }
+        if separator.is_empty() {
+            return Err(ConfigError::InvalidOperation("--split needs a non-empty separator".to_string()));
+        }
+        values = values
+            .iter()
+            .flat_map(|value| value.split(separator.as_str()))
+            .filter(|field| !field.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+
+    if args.trim && !matches!(operation, Operation::Set | Operation::Add) {
+        return Err(ConfigError::InvalidOperation("--trim only applies to --set and --add".to_string()));
+    }
+
+    if args.json.is_some() && !values.is_empty() {
+        return Err(ConfigError::InvalidOperation(
+            "--set --json does not take values".to_string(),
+        ));
+    }
+
+    let needs_values = match operation {
+        Operation::Set => args.json.is_none() && pairs.is_empty(),
+        Operation::Add => true,
+        Operation::Del => args.index.is_none(),
+        _ => false,
+    };
+    if needs_values && values.is_empty() {
+        let op_name = format!("{:?}", operation).to_lowercase();
+        return Err(ConfigError::InvalidOperation(
+            format!("--{} requires at least one value", op_name),
+        ));
+    }
+
+    if matches!(operation, Operation::Replace | Operation::RenameValue) && values.len() != 2 {
+        let op_name = if matches!(operation, Operation::Replace) { "replace" } else { "rename-value" };
+        return Err(ConfigError::InvalidOperation(format!(
+            "--{} requires exactly two values: OLD NEW",
+            op_name
+        )));
+    }
+
+    if (args.keys_only || args.values_only) && !key.is_empty() && !key.contains('*') {
+        return Err(ConfigError::InvalidOperation(
+            "--keys-only and --values-only only apply to a whole-section or pattern --list".to_string(),
+        ));
+    }
+
+    if args.watch {
+        if !matches!(operation, Operation::List | Operation::ListBuilds | Operation::Keys) {
+            return Err(ConfigError::InvalidOperation(
+                "--watch only applies to read operations (--list, --list-builds, --keys)".to_string(),
+            ));
+        }
+        if !cfg!(feature = "watch") {
+            return Err(ConfigError::InvalidOperation(
+                "--watch requires c2rust-config to be built with the `watch` feature".to_string(),
+            ));
+        }
+    }
+
+    if all_features && !matches!(operation, Operation::List | Operation::ListBuilds | Operation::Keys | Operation::Unset) {
+        return Err(ConfigError::InvalidOperation(
+            "--feature all only applies to read operations (--list, --list-builds, --keys) and --unset <key>".to_string(),
+        ));
+    }
+
+    if args.lenient && !matches!(operation, Operation::List | Operation::ListBuilds | Operation::Keys) {
+        return Err(ConfigError::InvalidOperation(
+            "--lenient only applies to read operations (--list, --list-builds, --keys)".to_string(),
+        ));
+    }
+
+    if args.output.is_some() && matches!(operation, Operation::Exists) {
+        return Err(ConfigError::InvalidOperation(
+            "--output does not apply to --exists".to_string(),
+        ));
+    }
+
+    if args.count && key.is_empty() {
+        return Err(ConfigError::InvalidOperation("--count requires a key".to_string()));
+    }
+
+    if args.limit.is_some() && key.is_empty() {
+        return Err(ConfigError::InvalidOperation("--limit requires a key".to_string()));
+    }
+
+    if args.raw && key.is_empty() {
+        return Err(ConfigError::InvalidOperation("--raw requires a key".to_string()));
+    }
+
+    let options = Options {
+        value_type: args.value_type.unwrap_or_default(),
+        if_absent: args.if_absent,
+        expect_current: args.expect_current,
+        infer: args.infer,
+        force_array: args.force_array,
+        json: args.json,
+        trim: args.trim,
+        comment: args.comment,
+        count: args.count,
+        limit: args.limit,
+        nested: args.nested,
+        as_array: args.as_array,
+        raw: args.raw,
+        sort: args.sort,
+        create: args.create,
+        with_defaults: args.with_defaults,
+        force: args.force,
+        recursive: args.recursive,
+        keys_only: args.keys_only,
+        values_only: args.values_only,
+        types: args.types,
+        expand: args.expand,
+        expand_strict: args.expand_strict,
+        prefix: args.prefix,
+        yes: args.yes,
+        at: args.at,
+        index: args.index,
+        unique: args.unique,
+        quiet: args.quiet,
+        verbose: args.verbose,
+    };
+
+    // `--section global` and `--section model` accept any key, so flag writes that belong in a feature
+    let reads = matches!(operation, Operation::List | Operation::ListBuilds | Operation::Keys | Operation::Exists);
+    let section = match &target {
+        Target::Sections(sections) if mode == "section" => sections[0].as_str(),
+        _ => "",
+    };
+    if matches!(section, "global" | "model") && !reads {
+        let keys = if pairs.is_empty() {
+            vec![key.as_str()]
+        } else {
+            pairs.iter().map(|(key, _)| key.as_str()).collect()
+        };
+        for key in keys.into_iter().filter(|key| validate::is_feature_key(key)) {
+            options.warn(&format!(
+                "Warning: '{}' is a feature key but targets [{}]; use --make to configure a feature",
+                key, section
+            ));
+        }
+    }
+
+    Ok(Invocation {
+        operation,
+        target,
+        key,
+        values,
+        pairs,
+        options,
+        mode,
+        lenient: args.lenient,
+        log: args.log,
+        output: args.output,
+    })
+}
+
+/// Split a batch line into arguments like a POSIX shell would:
+/// whitespace separates words, quotes group them and a backslash escapes the next character
+fn split_line(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err("trailing backslash".to_string()),
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Apply an invocation to each of its sections in turn, stopping at the first error
+/// Listings go to stdout or the `--output` file, and listings over several features
+/// print a `[section]` header before each one. `--feature all --unset` reports each
+/// feature the key was removed from.
+/// Returns the sections that were modified, so an empty list means the config is unchanged.
+fn apply_invocation(config: &mut Config, invocation: &Invocation) -> Result<Vec<String>, ConfigError> {
+    let sections = invocation.target.sections(config)?;
+    let grouped = (sections.len() > 1 || invocation.all_features()) && invocation.lists();
+    let mut out: Box<dyn Write> = match &invocation.output {
+        Some(path) if invocation.lists() => Box::new(create_output(path)?),
+        _ => Box::new(std::io::stdout()),
+    };
+    let mut changed = Vec::new();
+    let mut printed = 0;
+    for section in &sections {
+        if invocation.all_features() && !invocation.lists() {
+            // `--feature all --unset` removes the key from every feature that has it
+            let nested = format!("{}.", invocation.key);
+            let present = config.keys(section)?.iter().any(|key| {
+                *key == invocation.key || (invocation.options.prefix && key.starts_with(&nested))
+            });
+            if !present {
+                invocation.options.warn(&format!("Note: '{}' is not set in {}", invocation.key, section));
+                continue;
+            }
+            if operations::apply(
+                config,
+                invocation.operation,
+                section,
+                &invocation.key,
+                Vec::new(),
+                &invocation.options,
+                &mut out,
+            )? {
+                changed.push(section.clone());
+            }
+            writeln!(out, "Unset '{}' in {}", invocation.key, section)?;
+            printed += 1;
+            continue;
+        }
+        if !grouped {
+            let mut mutated = false;
+            for (key, values) in invocation.targets() {
+                mutated |= operations::apply(
+                    config,
+                    invocation.operation,
+                    section,
+                    key,
+                    values.to_vec(),
+                    &invocation.options,
+                    &mut out,
+                )?;
+            }
+            if mutated {
+                changed.push(section.clone());
+            }
+            continue;
+        }
+        // Buffer each section so a feature skipped by `--feature all` leaves no header behind
+        let mut listing = Vec::new();
+        match operations::apply(
+            config,
+            invocation.operation,
+            section,
+            &invocation.key,
+            invocation.values.clone(),
+            &invocation.options,
+            &mut listing,
+        ) {
+            Err(ConfigError::KeyNotFound(key)) if invocation.all_features() => {
+                invocation.options.warn(&format!("Note: '{}' is not set in {}", key, section));
+                continue;
+            }
+            result => result?,
+        };
+        if printed > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "[{}]", section)?;
+        out.write_all(&listing)?;
+        printed += 1;
+    }
+    if invocation.all_features() && printed == 0 && !invocation.key.is_empty() {
+        return Err(ConfigError::KeyNotFound(invocation.key.clone()));
+    }
+    Ok(changed)
+}
+
+/// Print [`validate::expected_structure`] as aligned text or JSON
+fn print_config_schema(format: SchemaFormat) {
+    let sections = validate::expected_structure();
+    match format {
+        SchemaFormat::Text => {
+            let width = sections
+                .iter()
+                .flat_map(|section| section.keys.iter().map(|(key, _)| key.len()))
+                .max()
+                .unwrap_or(0);
+            for (i, section) in sections.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("[{}] - {}", section.name, section.description);
+                for (key, description) in &section.keys {
+                    println!("  {:width$}  {}", key, description, width = width);
+                }
+            }
+        }
+        SchemaFormat::Json => {
+            let schema: serde_json::Map<String, serde_json::Value> = sections
+                .into_iter()
+                .map(|section| {
+                    let keys: serde_json::Map<String, serde_json::Value> =
+                        section.keys.into_iter().map(|(key, description)| (key, description.into())).collect();
+                    let value = serde_json::json!({ "description": section.description, "keys": keys });
+                    (section.name.to_string(), value)
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&schema).unwrap_or_default());
+        }
+    }
+}
+
+/// Create (or truncate) an `--output` file, creating its parent directories as needed
+fn create_output(path: &Path) -> Result<fs::File, ConfigError> {
+    create_parent_dirs(path)?;
+    Ok(fs::File::create(path)?)
+}
+
+/// Create the missing parent directories of a file that is about to be written
+fn create_parent_dirs(path: &Path) -> Result<(), ConfigError> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+/// Append one JSON line per changed section to the `--log` file describing a mutation that was saved
+fn log_invocation(invocation: &Invocation, sections: &[String]) -> Result<(), ConfigError> {
+    let Some(path) = &invocation.log else {
+        return Ok(());
+    };
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    for section in sections {
+        for (key, values) in invocation.targets() {
+            let entry = serde_json::json!({
+                "time": time,
+                "mode": invocation.mode,
+                "section": section,
+                "key": key,
+                "operation": format!("{:?}", invocation.operation).to_lowercase(),
+                "values": values,
+            });
+            writeln!(file, "{}", entry)?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse one batch line into an invocation; the leading `config` word is optional
+fn parse_batch_line(line: &str) -> Result<Invocation, String> {
+    let mut words = split_line(line)?;
+    if words.first().map(String::as_str) != Some("config") {
+        words.insert(0, "config".to_string());
+    }
+    let cli = Cli::try_parse_from(std::iter::once("c2rust-config".to_string()).chain(words))
+        .map_err(|e| e.to_string().lines().next().unwrap_or_default().to_string())?;
+    let invocation = match cli.command {
+        Commands::Config(args) => resolve(*args).map_err(|e| e.to_string())?,
+        _ => return Err("only config commands can be batched".to_string()),
+    };
+    // The batch is saved once to config.toml, so a line cannot redirect its own save
+    if invocation.output.is_some() && !invocation.lists() {
+        return Err("--output cannot redirect a mutation inside a batch".to_string());
+    }
+    Ok(invocation)
+}
+
+/// Apply newline-delimited config commands from stdin to one in-memory config and save once
+/// Blank lines and lines starting with `#` are ignored. Any failing line aborts the batch
+/// before anything is written, unless `continue_on_error` is set.
+fn batch(continue_on_error: bool) -> Result<(), ConfigError> {
+    let input = std::io::read_to_string(std::io::stdin())?;
+    let mut invocations = Vec::new();
+    let mut failed = 0;
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_batch_line(line) {
+            Ok(invocation) => invocations.push((index + 1, invocation)),
+            Err(e) if continue_on_error => {
+                eprintln!("{}", style::error(&format!("line {}: {}", index + 1, e)));
+                failed += 1;
+            }
+            Err(e) => {
+                return Err(ConfigError::InvalidOperation(format!("line {}: {}", index + 1, e)));
+            }
+        }
+    }
+
+    let mut config = Config::load()?;
+    let mut applied = Vec::new();
+    for (number, invocation) in invocations {
+        match apply_invocation(&mut config, &invocation) {
+            Ok(changed) if !changed.is_empty() => applied.push((invocation, changed)),
+            Ok(_) => {}
+            Err(e) if continue_on_error => {
+                eprintln!("{}", style::error(&format!("line {}: {}", number, e)));
+                failed += 1;
+            }
+            Err(e) => {
+                eprintln!("Batch aborted at line {}, nothing was written", number);
+                return Err(e);
+            }
+        }
+    }
+
+    if !applied.is_empty() {
+        config.save()?;
+        for (invocation, sections) in &applied {
+            log_invocation(invocation, sections)?;
+        }
+    }
+    if failed > 0 {
+        return Err(ConfigError::InvalidOperation(format!("{} batch line(s) failed", failed)));
+    }
+    Ok(())
+}
+
+/// Print completion candidates, one per line
+/// Never creates files and prints nothing if the config cannot be read
+fn complete(args: CompleteArgs) {
+    let Ok(config) = Config::resolve_path().and_then(|path| Config::load_from(&path)) else {
+        return;
+    };
+
+    let candidates = match args.target {
+        CompleteTarget::Features => config.feature_names(),
+        CompleteTarget::Keys => {
+            let section = if args.global {
+                "global".to_string()
+            } else if args.model {
+                "model".to_string()
+            } else {
+                match Config::feature_section(args.feature.as_deref().unwrap_or(config.default_feature())) {
+                    Ok(section) => section,
+                    Err(_) => return,
+                }
+            };
+            config
+                .list_all(&section)
+                .map(|entries| entries.into_iter().map(|(key, _)| key).collect())
+                .unwrap_or_default()
+        }
+    };
+
+    for candidate in candidates {
+        println!("{}", candidate);
+    }
+}
+
+/// Value of `config --mode`
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Mode {
+    Global,
+    Model,
+    Make,
 }
 
 #[derive(Args)]
 struct ConfigArgs {
     /// Global configuration (e.g., compiler settings)
-    #[arg(long, group = "mode")]
+    #[arg(long, group = "mode", default_value_if("mode_name", "global", "true"))]
     global: bool,
 
     /// Model-related configuration
-    #[arg(long, group = "mode")]
+    #[arg(long, group = "mode", default_value_if("mode_name", "model", "true"))]
     model: bool,
 
     /// Build/clean/test-related configuration
-    #[arg(long, group = "mode")]
+    #[arg(long, group = "mode", default_value_if("mode_name", "make", "true"))]
     make: bool,
 
-    /// Feature name (default: "default") - only for --make
+    /// Section to operate on, as an alternative to --global, --model or --make
+    #[arg(long = "mode", id = "mode_name", value_name = "MODE", value_enum, group = "mode")]
+    mode_name: Option<Mode>,
+
+    /// Create the feature if it does not exist yet (only "default" is created implicitly) - only for --make
     #[arg(long, requires = "make")]
-    feature: Option<String>,
+    create: bool,
+
+    /// Seed a newly created feature with empty placeholders for the build/clean/test keys - only for --make
+    #[arg(long, requires = "make")]
+    with_defaults: bool,
+
+    /// Set or add keys even if the [schema] section does not allow them
+    #[arg(long)]
+    force: bool,
+
+    /// Any other top-level section, e.g. `cache` for a `[cache]` table
+    #[arg(long, group = "mode", value_name = "NAME")]
+    section: Option<String>,
+
+    /// Feature name (default: "default"); repeat or separate with commas to act on several features, or use `all` (or `*`) for every feature - only for --make
+    #[arg(long, requires = "make", value_delimiter = ',')]
+    feature: Vec<String>,
 
     /// Set key-value(s)
-    #[arg(long, group = "operation")]
+    #[arg(long, groups = ["operation", "typed"])]
     set: bool,
 
-    /// Delete key-value
+    /// Delete key-value; with --make and no key, delete the whole feature section
     #[arg(long, group = "operation")]
     unset: bool,
 
     /// Add value(s) to array key
-    #[arg(long, group = "operation")]
+    #[arg(long, groups = ["operation", "typed"])]
     add: bool,
 
     /// Delete value(s) from array key
-    #[arg(long, group = "operation")]
+    #[arg(long, groups = ["operation", "typed"])]
     del: bool,
 
+    /// Replace array elements equal to OLD with NEW, keeping their position
+    #[arg(long, group = "operation")]
+    replace: bool,
+
+    /// Replace OLD with NEW in every array of the section (takes OLD NEW instead of a key)
+    #[arg(long, group = "operation")]
+    rename_value: bool,
+
+    /// Rewrite the section with its keys sorted alphabetically and consistent spacing, keeping comments
+    #[arg(long, group = "operation")]
+    canonical: bool,
+
+    /// Create the section if it does not exist; does nothing otherwise
+    #[arg(long, group = "operation")]
+    ensure: bool,
+
+    /// Exit 0 if the section (or the key, when given) exists and nonzero otherwise, printing nothing
+    #[arg(long, group = "operation")]
+    exists: bool,
+
     /// List all values in the section, or specific key if provided
     #[arg(long, group = "operation")]
     list: bool,
 
+    /// List each build.files.N group together with its build.options[N] entry
+    #[arg(long, group = "operation")]
+    list_builds: bool,
+
+    /// List only the key names of the section, sorted
+    #[arg(long, group = "operation")]
+    keys: bool,
+
+    /// Only set the key if it does not exist yet; otherwise do nothing and exit 0 - only for --set
+    #[arg(long, requires = "set")]
+    if_absent: bool,
+
+    /// Fail without writing unless the key currently equals VALUE - only for --set
+    #[arg(long, value_name = "VALUE", requires = "set", conflicts_with = "if_absent")]
+    expect_current: Option<String>,
+
+    /// Store the values as this TOML type instead of strings - only for --set, --add and --del
+    #[arg(long = "type", value_name = "TYPE", requires = "typed")]
+    value_type: Option<ValueType>,
+
+    /// Store values that look like integers, floats or booleans as those types - only for --set, --add and --del
+    #[arg(long, requires = "typed", conflicts_with = "value_type")]
+    infer: bool,
+
+    /// Store the values as an array even if there is only one - only for --set
+    #[arg(long, requires = "set")]
+    force_array: bool,
+
+    /// Treat the key and values as key=value pairs and set each of them - only for --set
+    #[arg(long, requires = "set", conflicts_with_all = ["json", "from_file", "stdin"])]
+    kv: bool,
+
+    /// Store this JSON value (scalar, array or object) instead of string values - only for --set
+    #[arg(long, value_name = "JSON", requires = "set", conflicts_with_all = ["value_type", "infer", "force_array"])]
+    json: Option<String>,
+
+    /// Use the contents of this file (without its trailing newline) as the value - only for --set
+    #[arg(long, value_name = "PATH", requires = "set", conflicts_with = "json")]
+    from_file: Option<PathBuf>,
+
+    /// Read the value from stdin for --set, or one value per line for --add
+    #[arg(long, conflicts_with_all = ["from_file", "json"])]
+    stdin: bool,
+
+    /// Strip leading and trailing whitespace from each value - only for --set and --add
+    #[arg(long)]
+    trim: bool,
+
+    /// Split each value on SEP into several values, dropping empty fields - only for --add and --del
+    #[arg(long, value_name = "SEP")]
+    split: Option<String>,
+
+    /// Write TEXT as a comment directly above the key - only for --set
+    #[arg(long, value_name = "TEXT", requires = "set")]
+    comment: Option<String>,
+
+    /// Print the number of elements of the key instead of its values - only for --list <key>
+    #[arg(long, requires = "list")]
+    count: bool,
+
+    /// Print at most N elements of the key, then a `... (M more)` line - only for --list <key>
+    #[arg(long, value_name = "N", requires = "list")]
+    limit: Option<usize>,
+
+    /// Pretty-print values holding tables (inline tables, arrays of tables) one sub-key per line - only for --list
+    #[arg(long, requires = "list")]
+    nested: bool,
+
+    /// Print scalars in the bracketed multi-line form used for arrays - only for --list
+    #[arg(long, requires = "list", conflicts_with_all = ["count", "keys_only", "values_only"])]
+    as_array: bool,
+
+    /// Print the key's value as TOML text, e.g. ["a", "b"] or "gcc" - only for --list <key>
+    #[arg(long, requires = "list", conflicts_with_all = ["count", "limit", "nested", "as_array", "expand", "expand_strict"])]
+    raw: bool,
+
+    /// Sort the listed keys alphabetically instead of using document order - only for --list
+    #[arg(long, requires = "list")]
+    sort: bool,
+
+    /// List every section, printing keys as section.key = value - only for --list, without a mode
+    #[arg(long, requires = "list")]
+    recursive: bool,
+
+    /// Print only the key names of the section - only for --list without a key
+    #[arg(long, requires = "list", conflicts_with_all = ["values_only", "types"])]
+    keys_only: bool,
+
+    /// Print only the values of the section, one per line - only for --list without a key
+    #[arg(long, requires = "list", conflicts_with = "types")]
+    values_only: bool,
+
+    /// Append the TOML type of each value, e.g. "(integer)" - only for --list
+    #[arg(long, requires = "list")]
+    types: bool,
+
+    /// Expand ${VAR} references from the environment in the listed values - only for --list
+    #[arg(long, requires = "list")]
+    expand: bool,
+
+    /// Like --expand, but fail if a referenced variable is not set - only for --list
+    #[arg(long, requires = "list")]
+    expand_strict: bool,
+
+    /// Treat the key as a dotted prefix and remove it and every key under it - only for --unset
+    #[arg(long, requires = "unset")]
+    prefix: bool,
+
+    /// Remove a feature section that still has keys without asking - only for --make --unset
+    #[arg(long, short, requires = "unset")]
+    yes: bool,
+
+    /// Insert the values starting at this array index instead of appending - only for --add
+    #[arg(long, value_name = "INDEX", requires = "add")]
+    at: Option<usize>,
+
+    /// Delete the element at this array index instead of matching values - only for --del
+    #[arg(long, value_name = "INDEX", requires = "del")]
+    index: Option<usize>,
+
+    /// Also collapse duplicates already stored in the array and report how many were skipped - only for --add
+    #[arg(long, requires = "add")]
+    unique: bool,
+
+    /// Print the resolved config.toml path to stderr first; without an operation, print it to stdout and exit
+    #[arg(long)]
+    show_path: bool,
+
+    /// Keep running and re-print the listing whenever config.toml changes (needs the `watch` feature)
+    #[arg(long)]
+    watch: bool,
+
+    /// Write the listing to this file instead of stdout, or for mutations write the changed config here
+    /// instead of config.toml; parent directories are created
+    #[arg(long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Treat a missing .c2rust directory or config.toml as an empty config instead of failing - only for read operations
+    #[arg(long)]
+    lenient: bool,
+
+    /// Append a JSON line describing each saved mutation to this file; read-only operations are not logged
+    #[arg(long, value_name = "PATH")]
+    log: Option<PathBuf>,
+
+    /// Suppress warnings and notes on stderr; errors are still reported
+    #[arg(long, short)]
+    quiet: bool,
+
+    /// Print the resulting section after a mutating operation
+    #[arg(long, short)]
+    verbose: bool,
+
     /// Key to operate on
     key: Option<String>,
 
@@ -67,83 +1204,266 @@ struct ConfigArgs {
     values: Vec<String>,
 }
 
-fn main() {
-    if let Err(e) = run() {
-        eprintln!("{}", e);
-        std::process::exit(1);
+/// Operation flags of `config`, in the order `--help` lists them
+const OPERATION_FLAGS: [&str; 12] = [
+    "--set",
+    "--unset",
+    "--add",
+    "--del",
+    "--replace",
+    "--rename-value",
+    "--canonical",
+    "--ensure",
+    "--exists",
+    "--list",
+    "--list-builds",
+    "--keys",
+];
+
+/// Command-line flag selecting `operation`, e.g. `--list-builds` for [`Operation::ListBuilds`]
+fn operation_flag(operation: Operation) -> String {
+    let mut flag = String::from("--");
+    for c in format!("{:?}", operation).chars() {
+        if c.is_ascii_uppercase() && flag.len() > 2 {
+            flag.push('-');
+        }
+        flag.push(c.to_ascii_lowercase());
     }
+    flag
 }
 
-fn run() -> Result<(), ConfigError> {
-    let cli = Cli::parse();
-    let config = Config::load()?;
+impl ConfigArgs {
+    /// Number of operation flags given on the command line
+    fn operation_count(&self) -> usize {
+        [self.set, self.unset, self.add, self.del, self.replace, self.rename_value, self.canonical, self.ensure, self.exists, self.list, self.list_builds, self.keys]
+            .iter()
+            .filter(|&&x| x)
+            .count()
+    }
+}
 
+fn main() {
+    // clap exits with 2 on usage errors, which scripts would mistake for KeyNotFound
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            let code = match e.kind() {
+                clap::error::ErrorKind::DisplayHelp | clap::error::ErrorKind::DisplayVersion => 0,
+                _ => ConfigError::InvalidOperation(String::new()).exit_code(),
+            };
+            std::process::exit(code);
+        }
+    };
+    let error_format = cli.error_format;
+    if let Err(e) = run(cli) {
+        match error_format {
+            ErrorFormat::Text => eprintln!("{}", style::error(&e.to_string())),
+            ErrorFormat::Json => eprintln!(
+                "{}",
+                serde_json::json!({ "kind": e.kind_str(), "message": e.to_string(), "code": e.exit_code() })
+            ),
+        }
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run(cli: Cli) -> Result<(), ConfigError> {
     match cli.command {
-        Commands::Config(args) => {
-            // Validate exactly one mode is selected
-            let mode_count = [args.global, args.model, args.make].iter().filter(|&&x| x).count();
-            if mode_count != 1 {
-                return Err(ConfigError::InvalidOperation(
-                    "Exactly one of --global, --model, or --make must be specified".to_string(),
-                ));
+        Commands::Validate { strict } => {
+            let config = Config::load()?;
+            let warnings = validate::validate_all(&config, strict)?;
+            for warning in &warnings {
+                eprintln!("{}", style::warning(&format!("Warning: {}", warning)));
             }
-
-            // Validate exactly one operation is selected
-            let op_count = [args.set, args.unset, args.add, args.del, args.list].iter().filter(|&&x| x).count();
-            if op_count != 1 {
-                return Err(ConfigError::InvalidOperation(
-                    "Exactly one of --set, --unset, --add, --del, or --list must be specified".to_string(),
-                ));
+            if !warnings.is_empty() {
+                return Err(ConfigError::ValidationFailed(warnings.len()));
             }
-
-            // Validate --feature is only used with --make
-            if args.feature.is_some() && !args.make {
-                return Err(ConfigError::InvalidOperation(
-                    "--feature can only be used with --make".to_string(),
-                ));
+            println!("All {} feature(s) passed validation", config.feature_names().len());
+        }
+        Commands::Doctor { strict, fix } => {
+            if fix {
+                let mut config = Config::load()?;
+                let repairs = validate::doctor_fix(&mut config)?;
+                for repair in &repairs {
+                    println!("Fixed: {}", repair);
+                }
+                if !repairs.is_empty() {
+                    config.save()?;
+                }
             }
-
-            // Determine the section
-            let section = if args.global {
-                "global".to_string()
-            } else if args.model {
-                "model".to_string()
+            let problems = validate::doctor(&Config::load()?, strict)?;
+            let mut category = None;
+            for problem in &problems {
+                if category != Some(problem.category) {
+                    println!("[{}]", problem.category.title());
+                    category = Some(problem.category);
+                }
+                println!("  {}", problem.message);
+            }
+            if !problems.is_empty() {
+                return Err(ConfigError::ValidationFailed(problems.len()));
+            }
+            println!("No problems found");
+        }
+        Commands::Export { format, output, features_only, lenient } => {
+            let config = if lenient { Config::load_lenient()? } else { Config::load()? };
+            let tree = if features_only {
+                export::features_to_json(&config)
             } else {
-                let feature_name = args.feature.unwrap_or_else(|| "default".to_string()).to_lowercase();
-                format!("feature.{}", feature_name)
+                export::to_json(&config)
             };
+            let rendered = export::render(&tree, format)?;
+            match output {
+                Some(path) => create_output(&path)?.write_all(rendered.as_bytes())?,
+                None => print!("{}", rendered),
+            }
+        }
+        Commands::Import { path, format, overwrite } => {
+            let format = format.or_else(|| import::Format::from_path(&path)).ok_or_else(|| {
+                ConfigError::InvalidOperation(format!(
+                    "cannot infer the format of '{}', pass --format",
+                    path.display()
+                ))
+            })?;
+            let document = import::parse(&fs::read_to_string(&path)?, format)?;
 
-            // Determine and execute the operation
-            let operation = if args.set {
-                Operation::Set
-            } else if args.unset {
-                Operation::Unset
-            } else if args.add {
-                Operation::Add
-            } else if args.del {
-                Operation::Del
+            let mut config = Config::load()?;
+            let report = import::import(&mut config, &document, overwrite)?;
+            config.save()?;
+
+            for key in &report.skipped {
+                eprintln!("Skipped existing key '{}' (use --overwrite to replace it)", key);
+            }
+            eprintln!("Imported {} key(s), skipped {}", report.imported, report.skipped.len());
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "c2rust-config", &mut std::io::stdout());
+        }
+        Commands::PrintConfigSchema { format } => print_config_schema(format),
+        Commands::ListFeatures => {
+            for name in Config::load()?.feature_names() {
+                println!("{}", name);
+            }
+        }
+        Commands::MergeFeature { base, into, overwrite, append_arrays } => {
+            let base = Config::feature_section(&base)?;
+            let into = Config::feature_section(&into)?;
+            let mut config = Config::load()?;
+            let merged = config.merge_feature(&base, &into, overwrite, append_arrays)?;
+            config.save()?;
+            println!("Merged {} key(s) from {} into {}", merged, base, into);
+        }
+        Commands::RenameFeature { from, to, merge, overwrite, append_arrays } => {
+            let from = Config::feature_section(&from)?;
+            let to = Config::feature_section(&to)?;
+            let mut config = Config::load()?;
+            let merging = config.section_exists(&to) && from != to;
+            let merged = config.rename_feature(&from, &to, merge, overwrite, append_arrays)?;
+            config.save()?;
+            if merging {
+                println!("Merged {} key(s) from {} into {} and removed {}", merged, from, to, from);
             } else {
-                Operation::List
+                println!("Renamed {} to {}", from, to);
+            }
+        }
+        Commands::Diff { path, output, .. } => {
+            let config = Config::load()?;
+            let path = match path {
+                Some(path) => path,
+                None => Config::latest_backup()?,
             };
-
-            // Validate operation-specific requirements
-            let key = match operation {
-                Operation::List => args.key.unwrap_or_default(),
-                _ => args.key.ok_or_else(|| {
-                    let op_name = format!("{:?}", operation).to_lowercase();
-                    ConfigError::InvalidOperation(format!("--{} requires a key", op_name))
-                })?,
+            let old = Config::load_from(&path)?;
+            let render = |values: &Option<Vec<String>>| match values.as_deref() {
+                Some([value]) => value.clone(),
+                Some(values) => format!("[{}]", values.join(", ")),
+                None => String::new(),
             };
-
-            if matches!(operation, Operation::Set | Operation::Add | Operation::Del) && args.values.is_empty() {
-                let op_name = format!("{:?}", operation).to_lowercase();
-                return Err(ConfigError::InvalidOperation(
-                    format!("--{} requires at least one value", op_name),
-                ));
+            let mut out: Box<dyn Write> = match &output {
+                Some(path) => Box::new(create_output(path)?),
+                None => Box::new(std::io::stdout()),
+            };
+            for diff in config.diff_against(&old) {
+                match diff.kind {
+                    DiffKind::Added => writeln!(out, "+ {}.{} = {}", diff.section, diff.key, render(&diff.new))?,
+                    DiffKind::Removed => writeln!(out, "- {}.{} = {}", diff.section, diff.key, render(&diff.old))?,
+                    DiffKind::Changed => writeln!(
+                        out,
+                        "~ {}.{}: {} -> {}",
+                        diff.section,
+                        diff.key,
+                        render(&diff.old),
+                        render(&diff.new)
+                    )?,
+                }
+            }
+        }
+        Commands::Format => {
+            let mut config = Config::load()?;
+            config.format();
+            config.save()?;
+        }
+        Commands::Complete(args) => complete(args),
+        Commands::Tx { action } => match action {
+            TxAction::Begin => {
+                let staging_path = transaction::begin()?;
+                eprintln!("Transaction started, staging changes in {}", staging_path.display());
+            }
+            TxAction::Commit => transaction::commit()?,
+            TxAction::Abort => transaction::abort()?,
+        },
+        Commands::Config(args) if args.show_path && args.operation_count() == 0 => {
+            println!("{}", Config::resolve_path()?.display());
+        }
+        Commands::Config(args) => {
+            let show_path = args.show_path;
+            #[cfg(feature = "watch")]
+            let watch = args.watch;
+            let invocation = resolve(*args)?;
+            if matches!(invocation.operation, Operation::Exists) {
+                // Report only through the exit code, and never create config.toml
+                let result = Config::resolve_path()
+                    .and_then(|path| Config::load_from(&path))
+                    .and_then(|mut config| apply_invocation(&mut config, &invocation));
+                if let Err(e) = result {
+                    std::process::exit(e.exit_code());
+                }
+                return Ok(());
+            }
+            let mut config = if invocation.lenient { Config::load_lenient()? } else { Config::load()? };
+            if show_path {
+                eprintln!("Using {}", config.config_path().display());
+            }
+            // Several features are all updated in memory and written together
+            let changed = apply_invocation(&mut config, &invocation)?;
+            match &invocation.output {
+                // The changed copy always goes to --output, even if nothing changed
+                Some(path) if !invocation.lists() => {
+                    create_parent_dirs(path)?;
+                    config.save_to(path)?;
+                    log_invocation(&invocation, &changed)?;
+                }
+                _ if !changed.is_empty() => {
+                    config.save()?;
+                    log_invocation(&invocation, &changed)?;
+                }
+                _ => {}
+            }
+            #[cfg(feature = "watch")]
+            if watch {
+                let path = config.config_path().to_path_buf();
+                c2rust_config::watch::watch(&path, || {
+                    invocation.options.warn(&format!("{} changed", path.display()));
+                    // A half-written file is reported, and the next change is awaited
+                    let result = Config::load_from(&path).and_then(|mut config| apply_invocation(&mut config, &invocation));
+                    if let Err(e) = result {
+                        eprintln!("{}", style::error(&e.to_string()));
+                    }
+                    Ok(())
+                })?;
             }
-
-            operations::execute(config, operation, &section, &key, args.values)?;
         }
+        Commands::Batch { continue_on_error } => batch(continue_on_error)?,
     }
 
     Ok(())
@@ -1,62 +1,553 @@
-use crate::config::Config;
-use crate::error::Result;
+use crate::config::{Config, ValueType};
+use crate::error::{ConfigError, Result};
+use crate::export;
+use crate::import;
+use crate::style;
+use crate::validate;
+use serde_json::Value as JsonValue;
+use std::io::{IsTerminal, Write};
 
-#[derive(Debug)]
+/// Operation performed by [`execute`] on a configuration section
+#[derive(Debug, Clone, Copy)]
 pub enum Operation {
     Set,
     Unset,
     Add,
     Del,
+    Replace,
+    RenameValue,
+    Canonical,
+    Ensure,
+    Exists,
     List,
+    ListBuilds,
+    Keys,
 }
 
+/// Warn when a newly written key and another key of the section are prefixes of each other,
+/// e.g. `build` and `build.dir`, since the two cannot share one dotted form in config.toml
+fn warn_shadowing(config: &Config, section: &str, key: &str, options: &Options) -> Result<()> {
+    for other in config.shadowing_keys(section, key)? {
+        options.warn(&format!(
+            "Warning: '{}' and '{}' both exist in {}; they are kept as separate quoted keys",
+            key, other, section
+        ));
+    }
+    Ok(())
+}
+
+/// Convert positional values to TOML values using `--infer` or `--type` (strings by default)
+fn parse_values(values: &[String], options: &Options) -> Result<Vec<toml_edit::Value>> {
+    if options.infer {
+        Ok(values.iter().map(|raw| ValueType::infer(raw)).collect())
+    } else {
+        values.iter().map(|raw| options.value_type.parse(raw)).collect()
+    }
+}
+
+/// Fill a new feature with `key = ""` placeholders for every required key (see
+/// [`validate::required_feature_keys`]), each with a trailing comment describing what
+/// belongs there; `skip` names a key to leave out
+fn insert_feature_defaults(config: &mut Config, section: &str, skip: &str) -> Result<()> {
+    for key in validate::required_feature_keys(config).into_iter().filter(|key| key != skip) {
+        config.insert_placeholder(section, &key, &validate::required_key_description(&key))?;
+    }
+    Ok(())
+}
+
+/// Ask on the terminal whether a feature section that still holds keys should be removed
+/// Returns false without asking when stdin or stdout is not a terminal
+fn confirm_removal(section: &str, key_count: usize) -> Result<bool> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Ok(false);
+    }
+    eprint!("Remove '{}' and its {} key(s)? [y/N] ", section, key_count);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Modifiers that adjust how an operation behaves or renders its output
+#[derive(Debug, Default)]
+pub struct Options {
+    /// TOML type that `--set` and `--add` store their values as
+    pub value_type: ValueType,
+    /// Let `--set` and `--add` guess int, float and bool values instead of storing strings
+    pub infer: bool,
+    /// Leave the key untouched if it already exists instead of overwriting it with `--set`
+    pub if_absent: bool,
+    /// Only let `--set` proceed if the key currently holds exactly this value
+    pub expect_current: Option<String>,
+    /// Store the values of `--set` as an array even when there is only one
+    pub force_array: bool,
+    /// JSON value stored verbatim by `--set` instead of the positional values
+    pub json: Option<String>,
+    /// Strip leading and trailing whitespace from values stored by `--set` and `--add`
+    pub trim: bool,
+    /// Comment written above the key by `--set`
+    pub comment: Option<String>,
+    /// Print the number of elements of the listed key instead of its values
+    pub count: bool,
+    /// Print at most this many elements of the listed key, followed by how many were left out
+    pub limit: Option<usize>,
+    /// Pretty-print values that contain tables, one sub-key or element per line
+    pub nested: bool,
+    /// List scalars in the bracketed multi-line form used for arrays
+    pub as_array: bool,
+    /// Print the listed key's value as TOML text, quotes and brackets included
+    pub raw: bool,
+    /// Sort whole-section listings by key instead of using document order
+    pub sort: bool,
+    /// Allow `--set` and `--add` to create a feature section that does not exist yet
+    pub create: bool,
+    /// Seed a newly created feature with empty placeholders for the required keys
+    pub with_defaults: bool,
+    /// Set or add keys that the `[schema]` section does not allow
+    pub force: bool,
+    /// List every section, prefixing each key with its section name
+    pub recursive: bool,
+    /// Print only the key names of whole-section listings
+    pub keys_only: bool,
+    /// Print only the values of whole-section listings, one per line
+    pub values_only: bool,
+    /// Append the TOML type of each key to whole-section listings
+    pub types: bool,
+    /// Expand `${VAR}` references from the environment when listing
+    pub expand: bool,
+    /// Fail on unset variables while expanding instead of leaving them literal
+    pub expand_strict: bool,
+    /// Make `--unset` remove every key under the given dotted prefix
+    pub prefix: bool,
+    /// Remove a non-empty feature section without asking for confirmation
+    pub yes: bool,
+    /// Insert added values at this array index instead of appending
+    pub at: Option<usize>,
+    /// Delete the array element at this index instead of matching values
+    pub index: Option<usize>,
+    /// Collapse duplicates in the target array and report how many were skipped
+    pub unique: bool,
+    /// Suppress warnings and informational messages on stderr (errors still print)
+    pub quiet: bool,
+    /// Print the affected section after a successful mutation
+    pub verbose: bool,
+}
+
+impl Options {
+    /// Print a warning or note to stderr unless `quiet` is set
+    pub fn warn(&self, message: &str) {
+        if !self.quiet {
+            eprintln!("{}", style::warning(message));
+        }
+    }
+
+    /// Apply `${VAR}` expansion to a listed value when `expand` or `expand_strict` is set
+    fn render(&self, value: String) -> Result<String> {
+        if self.expand || self.expand_strict {
+            expand_vars(&value, self.expand_strict)
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+/// Replace `${VAR}` references with values from the environment
+/// Unset variables are left literal, or reported as an error when `strict` is set
+fn expand_vars(value: &str, strict: bool) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        let reference = &rest[start..start + 3 + len];
+        expanded.push_str(&rest[..start]);
+        match std::env::var(name) {
+            Ok(var) => expanded.push_str(&var),
+            Err(_) if strict => {
+                return Err(ConfigError::InvalidOperation(format!(
+                    "environment variable '{}' is not set",
+                    name
+                )));
+            }
+            Err(_) => expanded.push_str(reference),
+        }
+        rest = &rest[start + 3 + len..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Print every key of a section as `key = value`, with arrays in bracketed multi-line form
+/// Keys are printed as `<prefix><key>`
+fn print_section(config: &Config, section: &str, prefix: &str, options: &Options, out: &mut dyn Write) -> Result<()> {
+    print_entries(config, section, config.list_all(section)?, prefix, options, out)
+}
+
+/// Print listed entries of a section in the `key = value` form used by [`print_section`]
+fn print_entries(
+    config: &Config,
+    section: &str,
+    mut results: Vec<(String, Vec<String>)>,
+    prefix: &str,
+    options: &Options,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if options.sort {
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    for (key, values) in results {
+        if options.keys_only {
+            writeln!(out, "{}{}", prefix, key)?;
+            continue;
+        }
+        if options.values_only {
+            for value in values {
+                writeln!(out, "{}", options.render(value)?)?;
+            }
+            continue;
+        }
+        let annotation = if options.types {
+            format!(" ({})", config.type_name(section, &key)?)
+        } else {
+            String::new()
+        };
+        if options.nested {
+            let value = export::key_to_json(config, section, &key)?;
+            if holds_table(&value) {
+                let mut lines = Vec::new();
+                nested_lines(&value, 0, &format!("{}{} = ", prefix, key), &mut lines);
+                if let Some(last) = lines.last_mut() {
+                    last.push_str(&annotation);
+                }
+                for line in lines {
+                    writeln!(out, "{}", line)?;
+                }
+                continue;
+            }
+        }
+        if values.len() == 1 && !options.as_array {
+            writeln!(out, "{}{} = {}{}", prefix, key, options.render(values[0].clone())?, annotation)?;
+        } else {
+            writeln!(out, "{}{} = [", prefix, key)?;
+            for value in values {
+                writeln!(out, "  {}", options.render(value)?)?;
+            }
+            writeln!(out, "]{}", annotation)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a value is or contains a table, which the flat listing can only show as TOML text
+fn holds_table(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Object(_) => true,
+        JsonValue::Array(elements) => elements.iter().any(holds_table),
+        _ => false,
+    }
+}
+
+/// Lay out a value over several lines for `--nested`: arrays as `[ ... ]` with one element per
+/// line and tables as `{ ... }` with one `key = value` per line, each level indented two spaces
+fn nested_lines(value: &JsonValue, depth: usize, head: &str, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    match value {
+        JsonValue::Array(elements) => {
+            lines.push(format!("{}{}[", indent, head));
+            for element in elements {
+                nested_lines(element, depth + 1, "", lines);
+            }
+            lines.push(format!("{}]", indent));
+        }
+        JsonValue::Object(fields) => {
+            lines.push(format!("{}{}{{", indent, head));
+            for (key, field) in fields {
+                nested_lines(field, depth + 1, &format!("{} = ", key), lines);
+            }
+            lines.push(format!("{}}}", indent));
+        }
+        JsonValue::String(s) => lines.push(format!("{}{}{}", indent, head, s)),
+        other => lines.push(format!("{}{}{}", indent, head, other)),
+    }
+}
+
+/// After a mutation, print the resulting section when `verbose` is set
+fn echo_section(config: &Config, section: &str, options: &Options, out: &mut dyn Write) -> Result<()> {
+    if options.verbose {
+        print_section(config, section, "", options, out)?;
+    }
+    Ok(())
+}
+
+/// Run an operation against a section, saving the config for mutating operations
+/// and printing results to stdout for listing operations
 pub fn execute(
     mut config: Config,
     operation: Operation,
     section: &str,
     key: &str,
     values: Vec<String>,
+    options: &Options,
 ) -> Result<()> {
+    if apply(&mut config, operation, section, key, values, options, &mut std::io::stdout())? {
+        config.save()?;
+    }
+    Ok(())
+}
+
+/// Run an operation against the in-memory config without saving it, writing listings to `out`
+/// Returns whether the config was modified, so callers can save once after many operations
+pub fn apply(
+    config: &mut Config,
+    operation: Operation,
+    section: &str,
+    key: &str,
+    values: Vec<String>,
+    options: &Options,
+    out: &mut dyn Write,
+) -> Result<bool> {
+    let mut mutated = matches!(operation, Operation::Set | Operation::Unset | Operation::Add | Operation::Del | Operation::Replace | Operation::RenameValue | Operation::Canonical);
+
+    // Set and add are the operations that introduce new keys and sections
+    let writes = matches!(operation, Operation::Set | Operation::Add);
+    let values = if writes && options.trim {
+        values.into_iter().map(|value| value.trim().to_string()).collect()
+    } else {
+        values
+    };
+    if writes && !options.force && !config.schema_allows(section, key) {
+        return Err(ConfigError::InvalidOperation(format!(
+            "key '{}' is not allowed in '{}' by the [schema] section (pass --force to set it anyway)",
+            key, section
+        )));
+    }
+
+    // Only feature.default is created implicitly, so a typo'd --feature does not spawn a section
+    if let Some(feature_name) = section.strip_prefix("feature.") {
+        if writes && !config.section_exists(section) {
+            if let Some(similar) = config.similar_feature(feature_name) {
+                options.warn(&format!(
+                    "Warning: feature '{}' is very similar to existing feature '{}'; did you mean '{}'?",
+                    feature_name, similar, similar
+                ));
+            }
+            if feature_name != "default" && !options.create {
+                return Err(ConfigError::FeatureNotCreated(section.to_string()));
+            }
+            if options.with_defaults {
+                // With --if-absent, a placeholder for the key being set would count as already present
+                let skip = if options.if_absent { key } else { "" };
+                insert_feature_defaults(config, section, skip)?;
+            }
+        }
+    }
+
     match operation {
+        Operation::Set if options.if_absent && config.list(section, key).is_ok() => {
+            options.warn(&format!("Note: '{}' already exists in {}, leaving it unchanged", key, section));
+            mutated = false;
+        }
         Operation::Set => {
-            config.set(section, key, values)?;
-            config.save()?;
+            if let Some(expected) = &options.expect_current {
+                match config.list(section, key) {
+                    Ok(current) if current.len() == 1 && current[0] == *expected => {}
+                    Ok(current) => {
+                        return Err(ConfigError::InvalidOperation(format!(
+                            "expected '{}' to be '{}' but it is '{}'; nothing was changed",
+                            key,
+                            expected,
+                            current.join(", ")
+                        )));
+                    }
+                    Err(_) => {
+                        return Err(ConfigError::InvalidOperation(format!(
+                            "expected '{}' to be '{}' but it is not set; nothing was changed",
+                            key, expected
+                        )));
+                    }
+                }
+            }
+            let old_type = config.type_name(section, key).ok();
+            if let Some(json) = &options.json {
+                config.set_values(section, key, vec![import::parse_json_value(json)?])?;
+            } else {
+                let parsed = parse_values(&values, options)?;
+                if options.force_array || config.always_array(section, key) {
+                    config.set_array(section, key, parsed)?;
+                } else {
+                    config.set_values(section, key, parsed)?;
+                }
+            }
+            if let Some(comment) = &options.comment {
+                config.set_comment(section, key, comment)?;
+            }
+            let new_type = config.type_name(section, key)?;
+            match old_type {
+                Some(old_type) if old_type != new_type => options.warn(&format!(
+                    "Warning: '{}' changed type from {} to {}",
+                    key, old_type, new_type
+                )),
+                Some(_) => {}
+                None => warn_shadowing(config, section, key, options)?,
+            }
+            echo_section(config, section, options, out)?;
+        }
+        Operation::Unset if key.is_empty() => {
+            let keys = config.keys(section)?;
+            if !keys.is_empty() && !options.yes && !confirm_removal(section, keys.len())? {
+                return Err(ConfigError::InvalidOperation(format!(
+                    "not removing '{}', which still has {} key(s); pass --yes to confirm",
+                    section,
+                    keys.len()
+                )));
+            }
+            config.remove_feature(section)?;
+        }
+        Operation::Unset if options.prefix => {
+            let removed = config.unset_prefix(section, key)?;
+            options.warn(&format!("Removed {} key(s)", removed));
+            echo_section(config, section, options, out)?;
         }
         Operation::Unset => {
             config.unset(section, key)?;
-            config.save()?;
+            echo_section(config, section, options, out)?;
         }
         Operation::Add => {
-            config.add(section, key, values)?;
-            config.save()?;
+            let existed = config.type_name(section, key).is_ok();
+            let skipped = config.add_values(section, key, parse_values(&values, options)?, options.at, options.unique)?;
+            if !existed {
+                warn_shadowing(config, section, key, options)?;
+            }
+            if options.unique {
+                options.warn(&format!("Skipped {} duplicate value(s)", skipped));
+            }
+            echo_section(config, section, options, out)?;
         }
         Operation::Del => {
-            config.del(section, key, values)?;
-            config.save()?;
+            match config.del_values(section, key, parse_values(&values, options)?, options.index)? {
+                0 => options.warn("No matching values to remove"),
+                removed => options.warn(&format!("Removed {} value(s)", removed)),
+            }
+            echo_section(config, section, options, out)?;
+        }
+        Operation::Replace => {
+            let replaced = config.replace(section, key, &values[0], &values[1])?;
+            options.warn(&format!("Replaced {} element(s)", replaced));
+            echo_section(config, section, options, out)?;
+        }
+        Operation::RenameValue => {
+            let touched = config.rename_value(section, &values[0], &values[1])?;
+            for (key, replaced) in &touched {
+                options.warn(&format!("  {}: {} element(s)", key, replaced));
+            }
+            let total: usize = touched.iter().map(|(_, replaced)| replaced).sum();
+            options.warn(&format!("Replaced {} element(s) across {} key(s)", total, touched.len()));
+            echo_section(config, section, options, out)?;
+        }
+        Operation::Canonical => {
+            config.canonicalize_section(section)?;
+            echo_section(config, section, options, out)?;
+        }
+        Operation::Ensure => {
+            mutated = config.create_section(section)?;
+            if mutated && options.with_defaults {
+                insert_feature_defaults(config, section, "")?;
+            }
+            echo_section(config, section, options, out)?;
+        }
+        Operation::Exists if key.is_empty() => {
+            if !config.section_exists(section) {
+                return Err(ConfigError::FeatureNotFound(section.to_string()));
+            }
+        }
+        Operation::Exists => {
+            config.list(section, key)?;
+        }
+        Operation::List if options.recursive => {
+            for section in config.section_names() {
+                print_section(config, &section, &format!("{}.", section), options, out)?;
+            }
+        }
+        Operation::List if key.contains('*') => {
+            // A pattern such as `build.*` lists every matching key with its name
+            let matches = config.list_matching(section, key)?;
+            if options.count {
+                writeln!(out, "{}", matches.len())?;
+                return Ok(false);
+            }
+            print_entries(config, section, matches, "", options, out)?;
         }
         Operation::List => {
             // If a key is provided, only output that key's values
             if !key.is_empty() {
+                if options.raw {
+                    writeln!(out, "{}", config.raw(section, key)?)?;
+                    return Ok(false);
+                }
                 let value = config.list(section, key)?;
-                for v in value {
-                    println!("{}", v);
+                if options.count {
+                    writeln!(out, "{}", value.len())?;
+                    return Ok(false);
+                }
+                if options.nested {
+                    let tree = export::key_to_json(config, section, key)?;
+                    if holds_table(&tree) {
+                        let elements = match tree {
+                            JsonValue::Array(elements) => elements,
+                            other => vec![other],
+                        };
+                        let mut lines = Vec::new();
+                        for element in &elements {
+                            nested_lines(element, 0, "", &mut lines);
+                        }
+                        for line in lines {
+                            writeln!(out, "{}", line)?;
+                        }
+                        return Ok(false);
+                    }
+                }
+                let total = value.len();
+                let shown = options.limit.unwrap_or(total).min(total);
+                let indent = if options.as_array { "  " } else { "" };
+                if options.as_array {
+                    writeln!(out, "[")?;
+                }
+                for v in value.into_iter().take(shown) {
+                    writeln!(out, "{}{}", indent, options.render(v)?)?;
+                }
+                if shown < total {
+                    writeln!(out, "{}... ({} more)", indent, total - shown)?;
+                }
+                if options.as_array {
+                    writeln!(out, "]")?;
                 }
             } else {
                 // Otherwise, list all configurations
-                let results = config.list_all(section)?;
-                for (key, values) in results {
-                    if values.len() == 1 {
-                        println!("{} = {}", key, values[0]);
-                    } else {
-                        println!("{} = [", key);
-                        for value in values {
-                            println!("  {}", value);
-                        }
-                        println!("]");
+                print_section(config, section, "", options, out)?;
+            }
+        }
+        Operation::Keys => {
+            for key in config.keys(section)? {
+                writeln!(out, "{}", key)?;
+            }
+        }
+        Operation::ListBuilds => {
+            for group in config.build_groups(section)? {
+                match (group.files, group.options) {
+                    (Some(files), Some(options)) => {
+                        writeln!(out, "group {}: [{}] with options \"{}\"", group.index, files.join(", "), options)?;
                     }
+                    (Some(files), None) => {
+                        writeln!(out, "group {}: [{}] (unpaired: no build.options entry)", group.index, files.join(", "))?;
+                    }
+                    (None, Some(options)) => {
+                        writeln!(out, "group {}: with options \"{}\" (unpaired: no build.files.{} key)", group.index, options, group.index)?;
+                    }
+                    (None, None) => {}
                 }
             }
         }
     }
-    Ok(())
+    Ok(mutated)
 }
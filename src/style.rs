@@ -0,0 +1,30 @@
+use std::io::IsTerminal;
+
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether stderr output should be colored
+/// Color is only used on a terminal, and never when `NO_COLOR` is set to a non-empty value
+pub fn color_enabled() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && std::io::stderr().is_terminal()
+}
+
+fn paint(color: &str, message: &str) -> String {
+    if color_enabled() {
+        format!("{}{}{}", color, message, RESET)
+    } else {
+        message.to_string()
+    }
+}
+
+/// Style a warning or note for stderr (yellow)
+pub fn warning(message: &str) -> String {
+    paint(YELLOW, message)
+}
+
+/// Style an error message for stderr (red)
+pub fn error(message: &str) -> String {
+    paint(RED, message)
+}
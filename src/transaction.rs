@@ -0,0 +1,47 @@
+use crate::config::{Config, CONFIG_FILE, STAGING_FILE};
+use crate::error::{ConfigError, Result};
+use std::fs;
+use std::path::PathBuf;
+
+/// Paths of the live config file and its staging copy
+fn paths() -> Result<(PathBuf, PathBuf)> {
+    let c2rust_dir = Config::resolve_dir()?;
+    Ok((c2rust_dir.join(CONFIG_FILE), c2rust_dir.join(STAGING_FILE)))
+}
+
+/// Start a transaction by copying config.toml to the staging file
+/// All subsequent `config` operations act on the staging copy until commit or abort
+pub fn begin() -> Result<PathBuf> {
+    let (config_path, staging_path) = paths()?;
+    if staging_path.exists() {
+        return Err(ConfigError::InvalidOperation(format!(
+            "a transaction is already in progress ({})",
+            staging_path.display()
+        )));
+    }
+
+    // Loading ensures config.toml exists with the default sections
+    Config::load()?;
+    fs::copy(&config_path, &staging_path)?;
+    Ok(staging_path)
+}
+
+/// Apply the staged changes by replacing config.toml with the staging file
+pub fn commit() -> Result<()> {
+    let (config_path, staging_path) = paths()?;
+    if !staging_path.exists() {
+        return Err(ConfigError::InvalidOperation("no transaction in progress".to_string()));
+    }
+    fs::rename(&staging_path, &config_path)?;
+    Ok(())
+}
+
+/// Discard the staged changes, leaving config.toml untouched
+pub fn abort() -> Result<()> {
+    let (_, staging_path) = paths()?;
+    if !staging_path.exists() {
+        return Err(ConfigError::InvalidOperation("no transaction in progress".to_string()));
+    }
+    fs::remove_file(&staging_path)?;
+    Ok(())
+}
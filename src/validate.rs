@@ -0,0 +1,343 @@
+use crate::config::{Config, EntryValue, ALWAYS_ARRAY_RULE, NO_DUPLICATES_RULE, SCHEMA_SECTION};
+use crate::error::Result;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use toml_edit::Item;
+
+/// Top-level sections every config may have without being listed in `[schema]`
+const KNOWN_SECTIONS: [&str; 4] = ["global", "model", "feature", SCHEMA_SECTION];
+
+/// Keys that together make up a complete feature configuration
+pub const REQUIRED_FEATURE_KEYS: [&str; 6] = [
+    "clean.dir",
+    "clean.cmd",
+    "test.dir",
+    "test.cmd",
+    "build.dir",
+    "build.cmd",
+];
+
+/// `[global]` array that replaces [`REQUIRED_FEATURE_KEYS`] for projects with their own
+/// definition of a complete feature
+pub const REQUIRED_KEYS_SETTING: &str = "required_feature_keys";
+
+/// Keys a complete feature must configure: `[global] required_feature_keys` when it is set,
+/// otherwise [`REQUIRED_FEATURE_KEYS`]
+pub fn required_feature_keys(config: &Config) -> Vec<String> {
+    config
+        .list("global", REQUIRED_KEYS_SETTING)
+        .unwrap_or_else(|_| REQUIRED_FEATURE_KEYS.iter().map(|key| key.to_string()).collect())
+}
+
+/// One-line description of a required feature key such as `build.dir` or `test.cmd`
+/// Other keys only come from `[global] required_feature_keys`, so the description points there
+pub fn required_key_description(key: &str) -> String {
+    match key.split_once('.') {
+        Some((stage, "dir")) => format!("directory the {} command runs in", stage),
+        Some((stage, "cmd")) => format!("command that runs the {} step", stage),
+        _ => format!("required by [global] {}", REQUIRED_KEYS_SETTING),
+    }
+}
+
+/// A section of the expected config structure with a description of it and of each key
+pub struct SchemaSection {
+    /// Section name; `feature.<name>` stands for every feature
+    pub name: &'static str,
+    pub description: &'static str,
+    /// `(key, description)` pairs in the order they are usually written
+    pub keys: Vec<(String, String)>,
+}
+
+/// The structure c2rust-config expects: the sections a new config.toml starts with, the keys
+/// [`validate_feature`] requires of a feature and the optional keys the tool understands
+pub fn expected_structure() -> Vec<SchemaSection> {
+    let owned = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs.iter().map(|(key, description)| (key.to_string(), description.to_string())).collect()
+    };
+    let mut feature_keys: Vec<(String, String)> = REQUIRED_FEATURE_KEYS
+        .iter()
+        .map(|key| (key.to_string(), required_key_description(key)))
+        .collect();
+    feature_keys.extend(owned(&[
+        ("build.files.N", "source files of build group N (N = 0, 1, 2, ...)"),
+        ("build.options", "compiler options, one entry per build.files.N group"),
+    ]));
+
+    vec![
+        SchemaSection {
+            name: "global",
+            description: "settings shared by every feature",
+            keys: owned(&[
+                ("compiler", "array of compilers the project is built with"),
+                ("default_feature", "feature --make uses when no --feature is given (default: default)"),
+                (REQUIRED_KEYS_SETTING, "keys a complete feature must set, replacing the built-in list"),
+            ]),
+        },
+        SchemaSection {
+            name: "model",
+            description: "settings of the AI model used for translation",
+            keys: owned(&[("api_key", "API key of the model service"), ("model_name", "name of the model")]),
+        },
+        SchemaSection {
+            name: "feature.<name>",
+            description: "one build feature; a new config.toml starts with feature.default",
+            keys: feature_keys,
+        },
+        SchemaSection {
+            name: SCHEMA_SECTION,
+            description: "optional restrictions on the keys of other sections",
+            keys: owned(&[
+                ("<section>", "patterns of the keys allowed in that section; `feature` covers every feature"),
+                (NO_DUPLICATES_RULE, "patterns of array keys whose elements must be unique"),
+                (ALWAYS_ARRAY_RULE, "section.key patterns that --set stores as arrays even with one value"),
+            ]),
+        },
+    ]
+}
+
+/// Whether a key configures a feature step (`build.*`, `clean.*`, `test.*`) and so only
+/// means something inside a `[feature.<name>]` section
+pub fn is_feature_key(key: &str) -> bool {
+    REQUIRED_FEATURE_KEYS
+        .iter()
+        .filter_map(|required| required.split_once('.'))
+        .any(|(step, _)| key.split_once('.').is_some_and(|(prefix, _)| prefix == step))
+}
+
+/// Check a feature section and return human-readable warnings
+///
+/// Reports a partially configured feature (some but not all of [`required_feature_keys`] set),
+/// gaps in the `build.files.N` indices, and `build.files.N` groups that have no
+/// matching `build.options[N]` entry, or the reverse, and duplicate elements in
+/// arrays that must be unique (see [`Config::requires_unique`]).
+/// With `strict`, required keys holding an empty value count as missing.
+pub fn validate_feature(config: &Config, section: &str, strict: bool) -> Result<Vec<String>> {
+    Ok(check_feature(config, section, strict)?
+        .into_iter()
+        .map(|problem| problem.message)
+        .collect())
+}
+
+/// The checks behind [`validate_feature`], each problem tagged with its category
+fn check_feature(config: &Config, section: &str, strict: bool) -> Result<Vec<Problem>> {
+    let entries = config.list_all(section)?;
+    let lookup: HashMap<&str, &Vec<String>> = entries
+        .iter()
+        .map(|(key, values)| (key.as_str(), values))
+        .collect();
+    let mut warnings = Vec::new();
+
+    let is_set = |key: &str| {
+        lookup
+            .get(key)
+            .map(|values| !strict || values.iter().any(|v| !v.is_empty()))
+            .unwrap_or(false)
+    };
+    let required = required_feature_keys(config);
+    let present = required.iter().filter(|key| lookup.contains_key(key.as_str())).count();
+    let missing: Vec<&str> = required.iter().map(String::as_str).filter(|key| !is_set(key)).collect();
+    if present > 0 && !missing.is_empty() {
+        warnings.push(Problem::new(Category::RequiredKeys, format!(
+            "Feature '{}' is missing required keys: {}. All of [{}] should be configured together.",
+            section,
+            missing.join(", "),
+            required.join(", ")
+        )));
+    }
+
+    let file_indices: BTreeSet<usize> = entries
+        .iter()
+        .filter_map(|(key, _)| key.strip_prefix("build.files.")?.parse().ok())
+        .collect();
+    let options_len = lookup.get("build.options").map(|values| values.len());
+    let groups = file_indices.iter().next_back().map(|max| max + 1).unwrap_or(0);
+
+    let missing_groups: Vec<String> = (0..groups)
+        .filter(|index| !file_indices.contains(index))
+        .map(|index| format!("build.files.{}", index))
+        .collect();
+    if !missing_groups.is_empty() {
+        warnings.push(Problem::new(Category::BuildGroups, format!(
+            "Feature '{}' has gaps in build.files indices: missing {}",
+            section,
+            missing_groups.join(", ")
+        )));
+    }
+
+    let mismatch = match options_len {
+        None if groups > 0 => Some(format!(
+            "Feature '{}' defines build.files.{} but has no build.options",
+            section,
+            groups - 1
+        )),
+        Some(len) if groups > len => Some(format!(
+            "Feature '{}' defines build.files.{} but build.options only has {} entries",
+            section,
+            groups - 1,
+            len
+        )),
+        Some(len) if len > groups => Some(format!(
+            "Feature '{}' has {} build.options entries but only {} build.files groups",
+            section, len, groups
+        )),
+        _ => None,
+    };
+    warnings.extend(mismatch.map(|message| Problem::new(Category::BuildGroups, message)));
+
+    for (key, values) in &entries {
+        if !config.requires_unique(key) {
+            continue;
+        }
+        let mut seen = HashSet::new();
+        let mut duplicates = Vec::new();
+        for value in values {
+            if !seen.insert(value) && !duplicates.contains(&value.as_str()) {
+                duplicates.push(value.as_str());
+            }
+        }
+        if !duplicates.is_empty() {
+            warnings.push(Problem::new(Category::Duplicates, format!(
+                "Feature '{}' has duplicate values in {}: {}",
+                section,
+                key,
+                duplicates.join(", ")
+            )));
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Validate every `[feature.*]` section, returning all warnings in document order
+pub fn validate_all(config: &Config, strict: bool) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    for name in config.feature_names() {
+        warnings.extend(validate_feature(config, &format!("feature.{}", name), strict)?);
+    }
+    Ok(warnings)
+}
+
+/// Kind of problem reported by [`doctor`], used to group the report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    /// A feature with some but not all of [`REQUIRED_FEATURE_KEYS`]
+    RequiredKeys,
+    /// `build.files.N` gaps or a `build.options` length that does not match
+    BuildGroups,
+    /// Duplicate elements in arrays that must be unique
+    Duplicates,
+    /// Keys written as nested tables or inline tables instead of dotted keys
+    Layout,
+    /// Top-level sections the tool does not know about
+    Sections,
+    /// Feature names that `--feature` cannot select
+    FeatureNames,
+}
+
+impl Category {
+    /// Heading of the category in the `doctor` report
+    pub fn title(self) -> &'static str {
+        match self {
+            Category::RequiredKeys => "required keys",
+            Category::BuildGroups => "build groups",
+            Category::Duplicates => "duplicates",
+            Category::Layout => "layout",
+            Category::Sections => "sections",
+            Category::FeatureNames => "feature names",
+        }
+    }
+}
+
+/// One problem found by [`doctor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub category: Category,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(category: Category, message: String) -> Self {
+        Problem { category, message }
+    }
+}
+
+/// Check the health of the whole config
+///
+/// Runs every check of [`validate_all`] and also reports keys stored as nested tables,
+/// top-level sections other than `global`, `model`, `feature` and `schema` that `[schema]`
+/// does not list, and feature names that are not lowercase `[a-z0-9._-]`.
+/// Problems are sorted by category, keeping document order within each.
+pub fn doctor(config: &Config, strict: bool) -> Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    let schema = config.document().get(SCHEMA_SECTION).and_then(Item::as_table);
+
+    for (name, item) in config.document().iter() {
+        if name == "feature" {
+            continue;
+        }
+        if !KNOWN_SECTIONS.contains(&name) && !schema.is_some_and(|schema| schema.contains_key(name)) {
+            problems.push(Problem::new(
+                Category::Sections,
+                format!("Unknown section '{}' ({}); list it in [schema] if it is intended", name, item.type_name()),
+            ));
+        }
+    }
+
+    if let Some(features) = config.document().get("feature").and_then(Item::as_table) {
+        // Loading already rejected a `feature.X` that is not a table
+        for (name, _) in features.iter() {
+            if Config::feature_section(name).ok().as_deref() != Some(&format!("feature.{}", name)) {
+                problems.push(Problem::new(
+                    Category::FeatureNames,
+                    format!(
+                        "Feature name '{}' must be lowercase, contain only a-z, 0-9, '.', '_' and '-', and not be 'all'",
+                        name
+                    ),
+                ));
+            }
+        }
+    }
+
+    for name in config.feature_names() {
+        let section = format!("feature.{}", name);
+        problems.extend(check_feature(config, &section, strict)?);
+        let nested: Vec<String> = config
+            .entries(&section)?
+            .into_iter()
+            .filter(|entry| entry.nested)
+            .map(|entry| entry.key)
+            .collect();
+        if !nested.is_empty() {
+            problems.push(Problem::new(
+                Category::Layout,
+                format!("Feature '{}' stores keys as nested tables: {}", section, nested.join(", ")),
+            ));
+        }
+    }
+
+    problems.sort_by_key(|problem| problem.category);
+    Ok(problems)
+}
+
+/// Apply the repairs that cannot lose information and describe each one
+///
+/// Duplicates are removed from arrays that must be unique, keeping the first occurrence.
+/// Nested tables need no change here: saving the config writes every key in dotted form.
+pub fn doctor_fix(config: &mut Config) -> Result<Vec<String>> {
+    let mut fixes = Vec::new();
+    for name in config.feature_names() {
+        let section = format!("feature.{}", name);
+        for entry in config.entries(&section)? {
+            if entry.nested {
+                fixes.push(format!("Rewrote {} in {} as a dotted key", entry.key, section));
+            }
+            if !matches!(entry.value, EntryValue::Array(_)) || !config.requires_unique(&entry.key) {
+                continue;
+            }
+            let removed = config.add(&section, &entry.key, Vec::new(), None, true)?;
+            if removed > 0 {
+                fixes.push(format!("Removed {} duplicate value(s) from {} in {}", removed, entry.key, section));
+            }
+        }
+    }
+    Ok(fixes)
+}
@@ -0,0 +1,41 @@
+//! Re-run work whenever a file changes, for `config --watch` (behind the `watch` feature)
+
+use crate::error::{ConfigError, Result};
+use notify::{Event, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Quiet period that must pass after a change before the callback runs,
+/// so a burst of writes from an editor triggers a single re-run
+pub const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Call `on_change` after every change to `path`, until the callback or the watcher fails
+/// The parent directory is watched so editors that replace the file are still followed.
+pub fn watch(path: &Path, mut on_change: impl FnMut() -> Result<()>) -> Result<()> {
+    let (sender, receiver) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+    watcher
+        .watch(path.parent().unwrap_or(path), RecursiveMode::NonRecursive)
+        .map_err(watch_error)?;
+
+    loop {
+        let event = receiver
+            .recv()
+            .map_err(|_| ConfigError::InvalidOperation("the file watcher stopped unexpectedly".to_string()))?
+            .map_err(watch_error)?;
+        let touches_file = event.paths.iter().any(|changed| changed.file_name() == path.file_name());
+        if event.kind.is_access() || !touches_file {
+            continue;
+        }
+
+        // Let the writes settle before re-running
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+        on_change()?;
+    }
+}
+
+/// Report a watcher failure as an I/O error
+fn watch_error(err: notify::Error) -> ConfigError {
+    ConfigError::IoError(std::io::Error::other(err))
+}
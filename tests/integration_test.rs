@@ -1,3 +1,6 @@
+// Most tests pass their arguments as `.args(&[...])`
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use std::fs;
@@ -20,6 +23,7 @@ fn setup_test_env() -> TempDir {
 fn get_cmd(temp_dir: &TempDir) -> Command {
     let mut cmd = Command::cargo_bin("c2rust-config").unwrap();
     cmd.current_dir(temp_dir.path());
+    cmd.env_remove("C2RUST_LANG");
     cmd
 }
 
@@ -34,6 +38,7 @@ fn test_no_c2rust_directory() {
     let temp_dir = TempDir::new().unwrap();
     let mut cmd = Command::cargo_bin("c2rust-config").unwrap();
     cmd.current_dir(temp_dir.path());
+    cmd.env("C2RUST_LANG", "zh");
     cmd.args(&["config", "--make", "--list"]);
     
     cmd.assert()
@@ -112,6 +117,16 @@ fn test_make_del_from_array() {
     assert!(config.contains("main.c"));
     assert!(config.contains("test.c"));
     assert!(!config.contains("debug.c"));
+
+    // Removing the first element leaves no stray space in front of the new first one
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--del", "build.files.0", "main.c"])
+        .assert()
+        .success();
+    assert_eq!(
+        read_config(&temp_dir),
+        "[global]\n\n[model]\n\n[feature.default]\nbuild.files.0 = [\"test.c\"]\n"
+    );
 }
 
 #[test]
@@ -189,7 +204,7 @@ fn test_make_with_custom_feature() {
     let temp_dir = setup_test_env();
     
     get_cmd(&temp_dir)
-        .args(&["config", "--make", "--feature", "debug", "--set", "build.dir", "debug_build"])
+        .args(["config", "--make", "--feature", "debug", "--create", "--set", "build.dir", "debug_build"])
         .assert()
         .success();
     
@@ -203,7 +218,7 @@ fn test_feature_name_lowercase() {
     let temp_dir = setup_test_env();
     
     get_cmd(&temp_dir)
-        .args(&["config", "--make", "--feature", "DEBUG", "--set", "build.dir", "build"])
+        .args(["config", "--make", "--feature", "DEBUG", "--create", "--set", "build.dir", "build"])
         .assert()
         .success();
     
@@ -289,6 +304,31 @@ fn test_nested_keys() {
     assert!(config.contains("-O0"));
 }
 
+#[test]
+fn test_nested_keys_in_new_feature() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "fresh", "--create", "--set", "build.options.debug", "-g"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "fresh", "--set", "build.options.release", "-O2"])
+        .assert()
+        .success();
+
+    // One [feature.fresh] header with the dotted keys beneath it, no nested [feature.fresh.build] tables
+    assert_eq!(
+        read_config(&temp_dir),
+        "[global]\n\n[model]\n\n[feature.fresh]\nbuild.options.debug = \"-g\"\nbuild.options.release = \"-O2\"\n"
+    );
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "fresh", "--list"])
+        .assert()
+        .success()
+        .stdout("build.options.debug = -g\nbuild.options.release = -O2\n");
+}
+
 #[test]
 fn test_complex_workflow() {
     let temp_dir = setup_test_env();
@@ -497,6 +537,57 @@ fn test_list_with_arrays() {
 
 // ===== Validation Tests =====
 
+#[test]
+fn test_mode_flag_matches_boolean_flags() {
+    let flagged = setup_test_env();
+    let by_mode = setup_test_env();
+
+    for (temp_dir, mode) in [(&flagged, ["--make"].as_slice()), (&by_mode, ["--mode", "make"].as_slice())] {
+        for args in [
+            ["--set", "build.cmd", "make"].as_slice(),
+            ["--feature", "debug", "--create", "--set", "build.dir", "debug"].as_slice(),
+        ] {
+            get_cmd(temp_dir)
+                .arg("config")
+                .args(mode)
+                .args(args)
+                .assert()
+                .success();
+        }
+    }
+    assert_eq!(read_config(&flagged), read_config(&by_mode));
+
+    get_cmd(&by_mode)
+        .args(["config", "--mode", "global", "--set", "compiler", "gcc"])
+        .assert()
+        .success();
+    get_cmd(&by_mode)
+        .args(["config", "--global", "--list", "compiler"])
+        .assert()
+        .success()
+        .stdout("gcc\n");
+}
+
+#[test]
+fn test_mode_flag_conflicts_with_boolean_flags() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--mode", "model", "--make", "--list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    get_cmd(&temp_dir)
+        .args(["config", "--mode", "model", "--feature", "debug", "--list"])
+        .assert()
+        .failure();
+    get_cmd(&temp_dir)
+        .args(["config", "--mode", "build", "--list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("possible values: global, model, make"));
+}
+
 #[test]
 fn test_validation_no_mode_specified() {
     let temp_dir = setup_test_env();
@@ -505,7 +596,7 @@ fn test_validation_no_mode_specified() {
         .args(&["config", "--set", "test", "value"])
         .assert()
         .failure()
-        .stderr(predicate::str::contains("Exactly one of --global, --model, or --make must be specified"));
+        .stderr(predicate::str::contains("Exactly one of --global, --model, --make, or --section must be specified"));
 }
 
 #[test]
@@ -528,7 +619,10 @@ fn test_validation_no_operation_specified() {
         .args(&["config", "--global", "test", "value"])
         .assert()
         .failure()
-        .stderr(predicate::str::contains("Exactly one of --set, --unset, --add, --del, or --list must be specified"));
+        .stderr(predicate::str::contains(
+            "Exactly one of --set, --unset, --add, --del, --replace, --rename-value, --canonical, \
+             --ensure, --exists, --list, --list-builds, or --keys must be specified",
+        ));
 }
 
 #[test]
@@ -568,7 +662,7 @@ fn test_validation_feature_with_make_works() {
     
     // This should succeed (validation should pass)
     get_cmd(&temp_dir)
-        .args(&["config", "--make", "--feature", "debug", "--set", "compiler", "gcc"])
+        .args(["config", "--make", "--feature", "debug", "--create", "--set", "compiler", "gcc"])
         .assert()
         .success();
 }
@@ -834,6 +928,28 @@ dir = "build"
     assert!(config.contains(r#""build.cmd" = "make VERBOSE=1""#) || config.contains(r#"build.cmd = "make VERBOSE=1""#));
 }
 
+#[test]
+fn test_list_key_from_nested_tables() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    let config_content = "[global]\n\n[model]\n\n[feature.default.build]\ncmd = \"make\"\n\n[feature.default.build.env]\nCC = \"gcc\"\n";
+    fs::write(&config_path, config_content).unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("make\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.env.CC"])
+        .assert()
+        .success()
+        .stdout("gcc\n");
+
+    // Listing does not rewrite the file
+    assert_eq!(read_config(&temp_dir), config_content);
+}
+
 #[test]
 fn test_set_single_key_no_duplicates() {
     let temp_dir = setup_test_env();
@@ -1068,6 +1184,7 @@ fn test_auto_discovery_not_found() {
     // Don't create .c2rust directory, so auto-discovery should fail
     let mut cmd = Command::cargo_bin("c2rust-config").unwrap();
     cmd.current_dir(temp_dir.path());
+    cmd.env("C2RUST_LANG", "zh");
     cmd.args(&["config", "--make", "--list"]);
     
     cmd.assert()
@@ -1105,3 +1222,3949 @@ fn test_auto_discovery_list_operation() {
         .success()
         .stdout(predicate::str::contains("compiler = gcc"));
 }
+
+// ===== Tests for --list-builds =====
+
+#[test]
+fn test_list_builds_pairs_files_with_options() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.0", "main.c", "common.c"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.1", "release.c"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.options", "-DDEBUG", "-O2"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list-builds"])
+        .assert()
+        .success()
+        .stdout("group 0: [main.c, common.c] with options \"-DDEBUG\"\ngroup 1: [release.c] with options \"-O2\"\n");
+}
+
+#[test]
+fn test_list_builds_flags_unpaired_entries() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.0", "main.c"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.1", "extra.c"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.options", "-DDEBUG"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list-builds"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("group 0: [main.c] with options \"-DDEBUG\""))
+        .stdout(predicate::str::contains("group 1: [extra.c] (unpaired: no build.options entry)"));
+}
+
+#[test]
+fn test_list_builds_rejects_key() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list-builds", "build.files"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--list-builds does not take a key"));
+}
+
+// ===== Tests for Exit Codes =====
+
+#[test]
+fn test_exit_code_key_not_found() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "missing"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_exit_code_feature_not_found() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "missing", "--list"])
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_exit_code_config_dir_not_found() {
+    let temp_dir = TempDir::new().unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list"])
+        .assert()
+        .code(4);
+}
+
+#[test]
+fn test_lenient_list_without_config() {
+    let temp_dir = TempDir::new().unwrap();
+
+    for mode in ["--global", "--make"] {
+        get_cmd(&temp_dir)
+            .args(["config", mode, "--lenient", "--list"])
+            .assert()
+            .success()
+            .stdout("");
+    }
+    get_cmd(&temp_dir)
+        .args(["export", "--lenient"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"global\""));
+
+    // A .c2rust directory without config.toml is read as empty and config.toml is not created
+    fs::create_dir(temp_dir.path().join(".c2rust")).unwrap();
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--lenient", "--list"])
+        .assert()
+        .success()
+        .stdout("");
+    assert!(!temp_dir.path().join(".c2rust/config.toml").exists());
+}
+
+#[test]
+fn test_lenient_does_not_apply_to_mutations() {
+    let temp_dir = TempDir::new().unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "gcc"])
+        .assert()
+        .code(4);
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--lenient", "--set", "compiler", "gcc"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--lenient only applies to read operations"));
+    assert!(!temp_dir.path().join(".c2rust").exists());
+}
+
+#[test]
+fn test_exit_code_invalid_operation() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set"])
+        .assert()
+        .code(5);
+}
+
+#[test]
+fn test_exit_code_usage_error() {
+    let temp_dir = setup_test_env();
+
+    // Usage errors must not share KeyNotFound's exit code 2
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--no-such-flag", "--list"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--no-such-flag"));
+    get_cmd(&temp_dir)
+        .args(["no-such-command"])
+        .assert()
+        .code(5);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--help"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("Usage"));
+}
+
+#[test]
+fn test_exit_code_toml_parse_error() {
+    let temp_dir = setup_test_env();
+    fs::write(temp_dir.path().join(".c2rust/config.toml"), "[global\n").unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list"])
+        .assert()
+        .code(6);
+}
+
+// ===== Tests for Message Language Selection =====
+
+#[test]
+fn test_lang_default_english_config_dir_not_found() {
+    let temp_dir = TempDir::new().unwrap();
+
+    get_cmd(&temp_dir)
+        .env_remove("C2RUST_LANG")
+        .args(["config", "--make", "--list"])
+        .assert()
+        .failure()
+        .stderr(
+            predicate::str::contains("Error: could not find a .c2rust directory")
+                .and(predicate::str::contains("Search started at"))
+                .and(predicate::str::contains("错误").not()),
+        );
+}
+
+#[test]
+fn test_lang_zh_key_not_found() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .env("C2RUST_LANG", "zh")
+        .args(["config", "--global", "--list", "missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("错误：未找到键 'missing'"));
+}
+
+#[test]
+fn test_lang_en_key_not_found() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .env("C2RUST_LANG", "en")
+        .args(["config", "--global", "--list", "missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Error: key 'missing' not found"));
+}
+
+// ===== Tests for Transactions =====
+
+#[test]
+fn test_tx_commit_applies_staged_changes() {
+    let temp_dir = setup_test_env();
+    let staging_path = temp_dir.path().join(".c2rust/config.toml.staging");
+
+    get_cmd(&temp_dir).args(["tx", "begin"]).assert().success();
+    assert!(staging_path.exists());
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "build"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    // Staged state is visible to --list but not yet written to config.toml
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("make\n");
+    assert!(!read_config(&temp_dir).contains("build.dir"));
+
+    get_cmd(&temp_dir).args(["tx", "commit"]).assert().success();
+
+    assert!(!staging_path.exists());
+    let config = read_config(&temp_dir);
+    assert!(config.contains("build.dir"));
+    assert!(config.contains("build.cmd"));
+}
+
+#[test]
+fn test_tx_abort_discards_staged_changes() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    get_cmd(&temp_dir).args(["tx", "begin"]).assert().success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "build"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir).args(["tx", "abort"]).assert().success();
+
+    assert!(!temp_dir.path().join(".c2rust/config.toml.staging").exists());
+    assert_eq!(read_config(&temp_dir), before);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.dir"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_tx_commit_without_begin_fails() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["tx", "commit"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no transaction in progress"));
+}
+
+// ===== Tests for --count =====
+
+#[test]
+fn test_count_array_key() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.0", "main.c", "debug.c", "common.c"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.files.0", "--count"])
+        .assert()
+        .success()
+        .stdout("3\n");
+}
+
+#[test]
+fn test_count_scalar_key() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "build"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.dir", "--count"])
+        .assert()
+        .success()
+        .stdout("1\n");
+}
+
+#[test]
+fn test_count_missing_key() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "missing", "--count"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("key 'missing' not found"));
+}
+
+#[test]
+fn test_list_limit() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.0", "a.c", "b.c", "c.c", "d.c", "e.c"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--limit", "2", "build.files.0"])
+        .assert()
+        .success()
+        .stdout("a.c\nb.c\n... (3 more)\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--limit", "5", "build.files.0"])
+        .assert()
+        .success()
+        .stdout("a.c\nb.c\nc.c\nd.c\ne.c\n");
+
+    // --count still reports the full length
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--limit", "2", "--count", "build.files.0"])
+        .assert()
+        .success()
+        .stdout("5\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--limit", "2"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--limit requires a key"));
+}
+
+#[test]
+fn test_list_as_array() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.0", "a.c", "b.c"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--as-array", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("[\n  make\n]\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--as-array", "build.files.0"])
+        .assert()
+        .success()
+        .stdout("[\n  a.c\n  b.c\n]\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--as-array"])
+        .assert()
+        .success()
+        .stdout("build.cmd = [\n  make\n]\nbuild.files.0 = [\n  a.c\n  b.c\n]\n");
+}
+
+#[test]
+fn test_list_raw() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "gcc", "clang"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--type", "int", "jobs", "4"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "compiler", "--raw"])
+        .assert()
+        .success()
+        .stdout("[\"gcc\", \"clang\"]\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "--raw", "jobs"])
+        .assert()
+        .success()
+        .stdout("4\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "--raw"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--raw requires a key"));
+}
+
+// ===== Tests for Listing Order =====
+
+#[test]
+fn test_list_preserves_document_order() {
+    let temp_dir = setup_test_env();
+
+    for (key, value) in [("zeta", "1"), ("alpha", "2"), ("mid", "3")] {
+        get_cmd(&temp_dir)
+            .args(["config", "--global", "--set", key, value])
+            .assert()
+            .success();
+    }
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list"])
+        .assert()
+        .success()
+        .stdout("zeta = 1\nalpha = 2\nmid = 3\n");
+}
+
+#[test]
+fn test_list_order_with_interleaved_dotted_keys() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        "[global]\n\n[model]\n\n[feature.default]\nbuild.dir = \"b\"\nclean.cmd = \"c\"\nbuild.cmd = \"m\"\n",
+    )
+    .unwrap();
+
+    // Keys with the same first part form one group, at the position of the first of them
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list"])
+        .assert()
+        .success()
+        .stdout("build.dir = b\nbuild.cmd = m\nclean.cmd = c\n");
+
+    // An unrelated change saves the file in that order too
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "jobs", "4"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).ends_with("[feature.default]\nbuild.dir = \"b\"\nbuild.cmd = \"m\"\nclean.cmd = \"c\"\n"));
+}
+
+#[test]
+fn test_list_sort() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "test.cmd", "make test"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.0", "main.c", "util.c"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "clean.cmd", "make clean"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--sort"])
+        .assert()
+        .success()
+        .stdout("build.files.0 = [\n  main.c\n  util.c\n]\nclean.cmd = make clean\ntest.cmd = make test\n");
+}
+
+// ===== Tests for --add --at =====
+
+#[test]
+fn test_add_at_start() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.options", "-O2", "-Wall"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "--at", "0", "build.options", "-std=c99"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.options"])
+        .assert()
+        .success()
+        .stdout("-std=c99\n-O2\n-Wall\n");
+}
+
+#[test]
+fn test_add_at_middle() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.options", "-O2", "-Wall"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.options", "--at", "1", "-g", "-DDEBUG"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.options"])
+        .assert()
+        .success()
+        .stdout("-O2\n-g\n-DDEBUG\n-Wall\n");
+}
+
+#[test]
+fn test_add_at_out_of_range() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.options", "-O2"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.options", "--at", "5", "-g"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("index 5 is out of range"));
+}
+
+// ===== Tests for --add --unique =====
+
+#[test]
+fn test_add_unique_reports_skipped() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--add", "--unique", "compiler", "gcc", "clang"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Skipped 0 duplicate value(s)"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--add", "--unique", "compiler", "clang", "msvc"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Skipped 1 duplicate value(s)"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "compiler"])
+        .assert()
+        .success()
+        .stdout("gcc\nclang\nmsvc\n");
+}
+
+#[test]
+fn test_add_unique_collapses_existing_duplicates() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        "[global]\ncompiler = [\"gcc\", \"clang\", \"gcc\"]\n\n[model]\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--add", "--unique", "compiler", "msvc"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Skipped 1 duplicate value(s)"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "compiler"])
+        .assert()
+        .success()
+        .stdout("gcc\nclang\nmsvc\n");
+}
+
+// ===== Tests for Operations on Nested Configs =====
+
+/// Helper to set up a config whose feature uses nested sub-tables
+fn setup_nested_env() -> TempDir {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        r#"[global]
+
+[model]
+
+[feature.default.build]
+cmd = "make"
+dir = "build"
+files.0 = ["main.c", "debug.c"]
+
+[feature.default.clean]
+cmd = "make clean"
+"#,
+    )
+    .unwrap();
+    temp_dir
+}
+
+#[test]
+fn test_unset_on_nested_config() {
+    let temp_dir = setup_nested_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--unset", "build.cmd"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.cmd"])
+        .assert()
+        .code(2);
+
+    let config = read_config(&temp_dir);
+    assert!(!config.contains(r#""make""#));
+    assert!(config.contains("make clean"));
+    assert!(config.contains("build.dir"));
+}
+
+#[test]
+fn test_del_on_nested_config() {
+    let temp_dir = setup_nested_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--del", "build.files.0", "debug.c"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.files.0"])
+        .assert()
+        .success()
+        .stdout("main.c\n");
+}
+
+#[test]
+fn test_inline_table_flattened_without_stray_spacing() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(
+        &config_path,
+        "[global]\n\n[model]\n\n[feature.default]\nbuild = { cmd = \"make\", dir = \"b\" }\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "test.cmd", "make test"])
+        .assert()
+        .success();
+    assert_eq!(
+        read_config(&temp_dir),
+        "[global]\n\n[model]\n\n[feature.default]\nbuild.cmd = \"make\"\nbuild.dir = \"b\"\ntest.cmd = \"make test\"\n"
+    );
+}
+
+// ===== Tests for validate =====
+
+#[test]
+fn test_validate_clean_config() {
+    let temp_dir = setup_test_env();
+
+    for (key, value) in [
+        ("build.dir", "build"),
+        ("build.cmd", "make"),
+        ("clean.dir", "build"),
+        ("clean.cmd", "make clean"),
+        ("test.dir", "build"),
+        ("test.cmd", "make test"),
+    ] {
+        get_cmd(&temp_dir)
+            .args(["config", "--make", "--set", key, value])
+            .assert()
+            .success();
+    }
+
+    get_cmd(&temp_dir)
+        .args(["validate"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Warning").not());
+}
+
+#[test]
+fn test_validate_half_configured_feature() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--create", "--set", "build.dir", "build"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--add", "build.files.0", "main.c"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["validate"])
+        .assert()
+        .code(7)
+        .stderr(
+            predicate::str::contains("Feature 'feature.debug' is missing required keys")
+                .and(predicate::str::contains("defines build.files.0 but has no build.options")),
+        );
+}
+
+#[test]
+fn test_validate_custom_required_keys() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(
+        &config_path,
+        "[global]\nrequired_feature_keys = [\"build.cmd\", \"lint.cmd\"]\n\n[model]\n\n\
+         [feature.default]\nbuild.cmd = \"make\"\n\n\
+         [feature.full]\nbuild.cmd = \"make\"\nlint.cmd = \"make lint\"\n",
+    )
+    .unwrap();
+
+    // Only the custom list counts: clean.* and test.* are no longer required
+    get_cmd(&temp_dir)
+        .args(["validate"])
+        .assert()
+        .code(7)
+        .stderr(
+            predicate::str::contains(
+                "Feature 'feature.default' is missing required keys: lint.cmd. All of [build.cmd, lint.cmd] should be configured together.",
+            )
+            .and(predicate::str::contains("feature.full").not())
+            .and(predicate::str::contains("validation found 1 warning(s)")),
+        );
+}
+
+#[test]
+fn test_validate_strict_empty_values() {
+    let temp_dir = setup_test_env();
+
+    for (key, value) in [
+        ("build.dir", "build"),
+        ("build.cmd", ""),
+        ("clean.dir", "build"),
+        ("clean.cmd", "make clean"),
+        ("test.dir", "build"),
+        ("test.cmd", "make test"),
+    ] {
+        get_cmd(&temp_dir)
+            .args(["config", "--make", "--set", key, value])
+            .assert()
+            .success();
+    }
+
+    get_cmd(&temp_dir).args(["validate"]).assert().success();
+
+    get_cmd(&temp_dir)
+        .args(["validate", "--strict"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing required keys: build.cmd"));
+}
+
+#[test]
+fn test_validate_build_files_gap() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.0", "main.c"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.2", "extra.c"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.options", "-O0", "-O1", "-O2"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["validate"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Feature 'feature.default' has gaps in build.files indices: missing build.files.1",
+        ));
+}
+
+// ===== Tests for doctor =====
+
+#[test]
+fn test_doctor_healthy_config() {
+    let temp_dir = setup_test_env();
+
+    for (key, value) in [
+        ("build.dir", "build"),
+        ("build.cmd", "make"),
+        ("clean.dir", "build"),
+        ("clean.cmd", "make clean"),
+        ("test.dir", "build"),
+        ("test.cmd", "make test"),
+    ] {
+        get_cmd(&temp_dir)
+            .args(["config", "--make", "--set", key, value])
+            .assert()
+            .success();
+    }
+
+    get_cmd(&temp_dir)
+        .args(["doctor"])
+        .assert()
+        .success()
+        .stdout("No problems found\n");
+}
+
+/// Helper that writes a config with one problem of each category
+fn setup_doctor_env() -> TempDir {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        r#"[global]
+
+[model]
+
+[cache]
+dir = "cache"
+
+[feature.default]
+build.cmd = "make"
+build.files.0 = ["a.c", "b.c", "a.c"]
+build.files.2 = ["c.c"]
+build.options = ["-O0"]
+
+[feature.Debug]
+
+[feature.nested.build]
+cmd = "make"
+dir = "build"
+"#,
+    )
+    .unwrap();
+    temp_dir
+}
+
+#[test]
+fn test_doctor_reports_every_problem() {
+    let temp_dir = setup_doctor_env();
+    let before = read_config(&temp_dir);
+
+    get_cmd(&temp_dir)
+        .args(["doctor"])
+        .assert()
+        .code(7)
+        .stdout(
+            predicate::str::contains("[required keys]\n  Feature 'feature.default' is missing required keys")
+                .and(predicate::str::contains("[build groups]\n  Feature 'feature.default' has gaps in build.files indices"))
+                .and(predicate::str::contains("build.options only has 1 entries"))
+                .and(predicate::str::contains("[duplicates]\n  Feature 'feature.default' has duplicate values in build.files.0: a.c"))
+                .and(predicate::str::contains("[layout]\n  Feature 'feature.nested' stores keys as nested tables: build.cmd, build.dir"))
+                .and(predicate::str::contains("[sections]\n  Unknown section 'cache'"))
+                .and(predicate::str::contains("[feature names]\n  Feature name 'Debug' must be lowercase")),
+        )
+        .stderr(predicate::str::contains("validation found 8 warning(s)"));
+
+    // Without --fix nothing is written
+    assert_eq!(read_config(&temp_dir), before);
+}
+
+#[test]
+fn test_doctor_fix() {
+    let temp_dir = setup_doctor_env();
+
+    get_cmd(&temp_dir)
+        .args(["doctor", "--fix"])
+        .assert()
+        .code(7)
+        .stdout(
+            predicate::str::contains("Fixed: Removed 1 duplicate value(s) from build.files.0 in feature.default\n")
+                .and(predicate::str::contains("Fixed: Rewrote build.cmd in feature.nested as a dotted key\n"))
+                .and(predicate::str::contains("[duplicates]").not())
+                .and(predicate::str::contains("[layout]").not())
+                .and(predicate::str::contains("[sections]")),
+        );
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("build.files.0 = [\"a.c\", \"b.c\"]\n"), "{}", content);
+    assert!(content.contains("[feature.nested]\nbuild.cmd = \"make\"\nbuild.dir = \"build\"\n"), "{}", content);
+}
+
+#[test]
+fn test_doctor_accepts_sections_listed_in_schema() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        "[global]\n\n[model]\n\n[schema]\ncache = [\"*\"]\n\n[cache]\ndir = \"cache\"\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["doctor"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_doctor_rejects_feature_that_is_not_a_table() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        "[global]\n\n[model]\n\n[feature]\nx = 1\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["doctor"])
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("'feature.x' must be a table"));
+}
+
+// ===== Tests for export =====
+
+#[test]
+fn test_export_json_nested() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        r#"[global]
+compiler = ["gcc"]
+
+[model]
+port = 8080
+
+[feature.default]
+"build.dir" = "build"
+"build.cmd" = "make"
+"build.files.0" = ["main.c"]
+"#,
+    )
+    .unwrap();
+
+    let output = get_cmd(&temp_dir)
+        .args(["export", "--format", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(json["global"]["compiler"], serde_json::json!(["gcc"]));
+    assert_eq!(json["model"]["port"], serde_json::json!(8080));
+    assert_eq!(json["feature"]["default"]["build"]["dir"], "build");
+    assert_eq!(json["feature"]["default"]["build"]["cmd"], "make");
+    assert_eq!(json["feature"]["default"]["build"]["files"]["0"], serde_json::json!(["main.c"]));
+}
+
+#[test]
+fn test_export_features_only() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        r#"[global]
+
+[model]
+
+[feature.default]
+"build.dir" = "build"
+"build.cmd" = "make"
+"build.files.0" = ["main.c"]
+"build.options" = ["-O2"]
+
+[feature.debug.build]
+dir = "build-debug"
+cmd = "make debug"
+"#,
+    )
+    .unwrap();
+
+    let output = get_cmd(&temp_dir)
+        .args(["export", "--features-only"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let features = json.as_array().unwrap();
+    assert_eq!(features.len(), 2);
+    assert_eq!(features[0]["name"], "default");
+    assert_eq!(features[0]["build"]["cmd"], "make");
+    assert_eq!(features[0]["build"]["files"]["0"], serde_json::json!(["main.c"]));
+    assert_eq!(features[0]["build"]["options"], serde_json::json!(["-O2"]));
+    assert_eq!(features[1]["name"], "debug");
+    assert_eq!(features[1]["build"]["cmd"], "make debug");
+}
+
+#[test]
+fn test_export_yaml_to_file() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    let output_path = temp_dir.path().join("config.yaml");
+    get_cmd(&temp_dir)
+        .args(["export", "--format", "yaml", "--output", output_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout("");
+
+    let yaml = fs::read_to_string(&output_path).unwrap();
+    assert!(yaml.contains("feature:"));
+    assert!(yaml.contains("cmd: make"));
+}
+
+// ===== Tests for import =====
+
+#[test]
+fn test_import_json_routes_sections() {
+    let temp_dir = setup_test_env();
+    let import_path = temp_dir.path().join("template.json");
+    fs::write(
+        &import_path,
+        r#"{
+  "global": { "compiler": ["gcc", "clang"] },
+  "model": { "port": 8080 },
+  "feature": {
+    "default": { "build": { "dir": "build", "cmd": "make" } },
+    "debug": { "build.cmd": "make DEBUG=1" }
+  }
+}"#,
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["import", import_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Imported 5 key(s), skipped 0"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "compiler"])
+        .assert()
+        .success()
+        .stdout("gcc\nclang\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--list", "port"])
+        .assert()
+        .success()
+        .stdout("8080\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.dir"])
+        .assert()
+        .success()
+        .stdout("build\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--list", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("make DEBUG=1\n");
+
+    let config = read_config(&temp_dir);
+    assert!(config.contains("port = 8080"));
+}
+
+#[test]
+fn test_import_conflicts_respect_overwrite() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    let import_path = temp_dir.path().join("template.toml");
+    fs::write(
+        &import_path,
+        "[feature.default]\n\"build.cmd\" = \"ninja\"\n\"build.dir\" = \"out\"\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["import", import_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Skipped existing key 'feature.default.build.cmd'",
+        ));
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("make\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.dir"])
+        .assert()
+        .success()
+        .stdout("out\n");
+
+    get_cmd(&temp_dir)
+        .args(["import", "--overwrite", import_path.to_str().unwrap()])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("ninja\n");
+}
+
+// ===== Tests for completions =====
+
+#[test]
+fn test_completions_bash() {
+    let temp_dir = TempDir::new().unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["completions", "bash"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("c2rust__config,config)"));
+}
+
+#[test]
+fn test_dynamic_completion_keys_and_features() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "build"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--create", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["__complete", "keys", "--make"])
+        .assert()
+        .success()
+        .stdout("build.dir\n");
+    get_cmd(&temp_dir)
+        .args(["__complete", "keys", "--make", "--feature", "debug"])
+        .assert()
+        .success()
+        .stdout("build.cmd\n");
+    get_cmd(&temp_dir)
+        .args(["__complete", "features"])
+        .assert()
+        .success()
+        .stdout("default\ndebug\n");
+}
+
+#[test]
+fn test_dynamic_completion_without_config() {
+    let temp_dir = TempDir::new().unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["__complete", "keys", "--global"])
+        .assert()
+        .success()
+        .stdout("");
+}
+
+// ===== Tests for --quiet =====
+
+#[test]
+fn test_quiet_set_on_half_configured_feature() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "--quiet", "build.dir", "build"])
+        .assert()
+        .success()
+        .stderr("");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "--unique", "--quiet", "build.options", "-O2"])
+        .assert()
+        .success()
+        .stderr("");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.dir"])
+        .assert()
+        .success()
+        .stdout("build\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.options"])
+        .assert()
+        .success()
+        .stdout("-O2\n");
+}
+
+#[test]
+fn test_quiet_still_reports_errors() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--quiet", "--list", "missing"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("key 'missing' not found"));
+}
+
+// ===== Tests for --verbose =====
+
+#[test]
+fn test_verbose_set_prints_section() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success()
+        .stdout("");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--verbose", "--set", "build.dir", "build"])
+        .assert()
+        .success()
+        .stdout("build.cmd = make\nbuild.dir = build\n");
+}
+
+#[test]
+fn test_verbose_add_prints_arrays() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--verbose", "--add", "compiler", "gcc", "clang"])
+        .assert()
+        .success()
+        .stdout("compiler = [\n  gcc\n  clang\n]\n");
+}
+
+// ===== Tests for Removing Feature Sections =====
+
+#[test]
+fn test_unset_removes_feature() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "experimental", "--create", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["list-features"])
+        .assert()
+        .success()
+        .stdout("experimental\ndefault\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "experimental", "--unset", "--yes"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["list-features"])
+        .assert()
+        .success()
+        .stdout("default\n");
+    assert!(!read_config(&temp_dir).contains("[feature.experimental]"));
+}
+
+#[test]
+fn test_create_feature_with_defaults() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--create", "--with-defaults", "--set", "build.cmd", "make release"])
+        .assert()
+        .success();
+
+    let content = read_config(&temp_dir);
+    for placeholder in [
+        "clean.dir = \"\" # directory the clean command runs in\n",
+        "clean.cmd = \"\" # command that runs the clean step\n",
+        "test.dir = \"\" # directory the test command runs in\n",
+        "test.cmd = \"\" # command that runs the test step\n",
+        "build.dir = \"\" # directory the build command runs in\n",
+    ] {
+        assert!(content.contains(placeholder), "missing {:?} in {}", placeholder, content);
+    }
+    // The key being set replaces its placeholder in place
+    assert!(content.contains("build.cmd = \"make release\" # command that runs the build step\n"), "{}", content);
+
+    // An existing feature is not seeded again
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--with-defaults", "--unset", "clean.dir"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--with-defaults", "--set", "clean.cmd", "make clean"])
+        .assert()
+        .success();
+    assert!(!read_config(&temp_dir).contains("clean.dir"));
+}
+
+#[test]
+fn test_create_feature_with_custom_required_keys() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        "[global]\nrequired_feature_keys = [\"build.cmd\", \"lint.args\"]\n\n[model]\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "lint", "--create", "--with-defaults", "--ensure"])
+        .assert()
+        .success();
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("build.cmd = \"\" # command that runs the build step\n"), "{}", content);
+    assert!(content.contains("lint.args = \"\" # required by [global] required_feature_keys\n"), "{}", content);
+    assert!(!content.contains("clean."), "{}", content);
+}
+
+#[test]
+fn test_ensure_feature_with_defaults() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--with-defaults", "--ensure"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--keys"])
+        .assert()
+        .success()
+        .stdout("build.cmd\nbuild.dir\nclean.cmd\nclean.dir\ntest.cmd\ntest.dir\n");
+}
+
+#[test]
+fn test_unset_non_empty_feature_requires_yes() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--create", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    // Not a terminal, so there is no prompt to answer
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--unset"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("pass --yes to confirm"));
+    assert!(read_config(&temp_dir).contains("[feature.release]"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--unset", "-y"])
+        .assert()
+        .success();
+    assert!(!read_config(&temp_dir).contains("[feature.release]"));
+}
+
+#[test]
+fn test_unset_empty_feature_without_yes() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "scratch", "--create", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "scratch", "--unset", "build.cmd"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "scratch", "--unset"])
+        .assert()
+        .success();
+    assert!(!read_config(&temp_dir).contains("[feature.scratch]"));
+}
+
+#[test]
+fn test_unset_missing_feature() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "nope", "--unset"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("feature 'feature.nope' not found"));
+}
+
+#[test]
+fn test_unset_without_key_requires_make() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--unset"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--unset requires a key"));
+}
+
+// ===== Tests for Typed --set =====
+
+#[test]
+fn test_set_typed_scalars() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "--type", "int", "port", "8080"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "--type", "bool", "stream", "true"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "--type", "float", "temperature", "0.7"])
+        .assert()
+        .success();
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("port = 8080\n"));
+    assert!(content.contains("stream = true\n"));
+    assert!(content.contains("temperature = 0.7\n"));
+}
+
+#[test]
+fn test_set_typed_invalid_value() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "--type", "int", "port", "eighty"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("'eighty' is not a valid int"));
+
+    assert!(!read_config(&temp_dir).contains("port"));
+}
+
+#[test]
+fn test_set_infer_types() {
+    let temp_dir = setup_test_env();
+
+    for (key, value) in [("port", "8080"), ("temperature", "3.14"), ("stream", "false"), ("code", "01"), ("name", "gpt-4")] {
+        get_cmd(&temp_dir)
+            .args(["config", "--model", "--set", "--infer", key, value])
+            .assert()
+            .success();
+    }
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("port = 8080\n"));
+    assert!(content.contains("temperature = 3.14\n"));
+    assert!(content.contains("stream = false\n"));
+    assert!(content.contains("code = \"01\"\n"));
+    assert!(content.contains("name = \"gpt-4\"\n"));
+}
+
+#[test]
+fn test_add_keeps_types() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--add", "--type", "int", "ports", "8080", "8443"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("ports = [8080, 8443]\n"));
+
+    // A string "8080" is a different element from the integer 8080
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--add", "--infer", "ports", "8080", "9000"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--add", "ports", "8080"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("ports = [8080, 8443, 9000, \"8080\"]\n"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--add", "--type", "int", "ports", "http"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_set_preserves_comments() {
+    let temp_dir = TempDir::new().unwrap();
+    let c2rust_dir = temp_dir.path().join(".c2rust");
+    fs::create_dir(&c2rust_dir).unwrap();
+    fs::write(
+        c2rust_dir.join("config.toml"),
+        "[global]\n# preferred compiler\ncompiler = \"gcc\" # comment\n\n[model]\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "clang"])
+        .assert()
+        .success();
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("# preferred compiler\ncompiler = [\"clang\"] # comment\n"));
+
+    // Comments above a nested table header or an inline table move to the first flattened key
+    fs::write(
+        c2rust_dir.join("config.toml"),
+        "[global]\n# toolchain\ntools = { cc = \"gcc\", cxx = \"g++\" }\n\n[model]\n\n\
+         [feature.default]\ndir = \"x\"\n\n# how to build\n[feature.default.build]\ncmd = \"make\"\n# output\ndir = \"b\"\n",
+    )
+    .unwrap();
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "jobs", "4"])
+        .assert()
+        .success();
+    assert_eq!(
+        read_config(&temp_dir),
+        "[global]\n# toolchain\ntools.cc = \"gcc\"\ntools.cxx = \"g++\"\njobs = \"4\"\n\n[model]\n\n\
+         [feature.default]\ndir = \"x\"\n\n# how to build\nbuild.cmd = \"make\"\n# output\nbuild.dir = \"b\"\n"
+    );
+}
+
+// ===== Tests for --keys =====
+
+#[test]
+fn test_keys_sorted() {
+    let temp_dir = setup_nested_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "test.cmd", "make test"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.bin", "app"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--keys"])
+        .assert()
+        .success()
+        .stdout("build.bin\nbuild.cmd\nbuild.dir\nbuild.files.0\nclean.cmd\ntest.cmd\n");
+}
+
+// ===== Tests for --expand =====
+
+#[test]
+fn test_list_expand_env_vars() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "${PROJECT_ROOT}/build"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .env("PROJECT_ROOT", "/src/app")
+        .args(["config", "--make", "--list", "--expand", "build.dir"])
+        .assert()
+        .success()
+        .stdout("/src/app/build\n");
+
+    get_cmd(&temp_dir)
+        .env("PROJECT_ROOT", "/src/app")
+        .args(["config", "--make", "--list", "--expand"])
+        .assert()
+        .success()
+        .stdout("build.dir = /src/app/build\n");
+
+    // The stored value stays literal
+    assert!(read_config(&temp_dir).contains("\"${PROJECT_ROOT}/build\""));
+}
+
+#[test]
+fn test_list_expand_unset_var() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "${C2RUST_TEST_UNSET}/build"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .env_remove("C2RUST_TEST_UNSET")
+        .args(["config", "--make", "--list", "--expand", "build.dir"])
+        .assert()
+        .success()
+        .stdout("${C2RUST_TEST_UNSET}/build\n");
+
+    get_cmd(&temp_dir)
+        .env_remove("C2RUST_TEST_UNSET")
+        .args(["config", "--make", "--list", "--expand-strict", "build.dir"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("environment variable 'C2RUST_TEST_UNSET' is not set"));
+}
+
+// ===== Tests for the Repository Boundary =====
+
+#[test]
+fn test_search_stops_at_git_boundary() {
+    // Outer checkout with its own .c2rust, inner repository without one
+    let temp_dir = setup_test_env();
+    fs::create_dir(temp_dir.path().join(".git")).unwrap();
+    let inner = temp_dir.path().join("vendor/inner");
+    fs::create_dir_all(inner.join(".git")).unwrap();
+    fs::create_dir_all(inner.join("src")).unwrap();
+
+    let mut cmd = Command::cargo_bin("c2rust-config").unwrap();
+    cmd.current_dir(inner.join("src"))
+        .env_remove("C2RUST_LANG")
+        .env_remove("C2RUST_BOUNDARY")
+        .args(["config", "--global", "--list"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains(format!(
+            "Stopped at the repository boundary: {}",
+            inner.display()
+        )));
+
+    // The search inside the inner repository finds its own .c2rust
+    fs::create_dir(inner.join(".c2rust")).unwrap();
+    let mut cmd = Command::cargo_bin("c2rust-config").unwrap();
+    cmd.current_dir(inner.join("src"))
+        .args(["config", "--global", "--set", "compiler", "clang"])
+        .assert()
+        .success();
+    assert!(fs::read_to_string(inner.join(".c2rust/config.toml")).unwrap().contains("clang"));
+    assert!(!read_config(&temp_dir).contains("clang"));
+}
+
+#[test]
+fn test_search_stops_at_custom_boundary() {
+    let temp_dir = setup_test_env();
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(&project).unwrap();
+    fs::write(project.join(".project-root"), "").unwrap();
+
+    let mut cmd = Command::cargo_bin("c2rust-config").unwrap();
+    cmd.current_dir(&project)
+        .env_remove("C2RUST_LANG")
+        .env("C2RUST_BOUNDARY", ".project-root")
+        .args(["config", "--global", "--list"])
+        .assert()
+        .code(4);
+}
+
+// ===== Tests for Batch Mode =====
+
+#[test]
+fn test_batch_applies_all_lines() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["batch"])
+        .write_stdin(
+            "# build settings\n\
+             config --make --set build.dir build\n\
+             --make --set build.cmd \"make -j4\"\n\
+             \n\
+             --global --set compiler 'gcc'\n",
+        )
+        .assert()
+        .success();
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("build.dir = \"build\""));
+    assert!(content.contains("build.cmd = \"make -j4\""));
+    assert!(content.contains("compiler = [\"gcc\"]"));
+}
+
+#[test]
+fn test_batch_aborts_without_writing() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    get_cmd(&temp_dir)
+        .args(["batch"])
+        .write_stdin("--make --set build.dir build\n--make --bogus\n")
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("line 2:"));
+    assert_eq!(read_config(&temp_dir), before);
+
+    get_cmd(&temp_dir)
+        .args(["batch"])
+        .write_stdin("--make --set build.dir build\n--global --unset missing.key\n--make --del build.dir x\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Batch aborted at line 3, nothing was written"));
+    assert_eq!(read_config(&temp_dir), before);
+}
+
+#[test]
+fn test_batch_continue_on_error() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["batch", "--continue-on-error"])
+        .write_stdin("--make --set build.dir build\n--make --bogus\n--make --set build.cmd make\n")
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("line 2:"))
+        .stderr(predicate::str::contains("1 batch line(s) failed"));
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("build.dir = \"build\""));
+    assert!(content.contains("build.cmd = \"make\""));
+}
+
+#[test]
+fn test_batch_default_feature_follows_earlier_line() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["batch"])
+        .write_stdin("--global --set default_feature rel
+--make --create --set build.dir b
+")
+        .assert()
+        .success();
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("[feature.rel]"));
+    assert!(!content.contains("[feature.default]"));
+}
+
+#[test]
+fn test_batch_feature_all_sees_earlier_feature() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["batch"])
+        .write_stdin(
+            "--make --feature debug --create --set build.dir b
+             --make --feature all --unset build.dir
+",
+        )
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Unset 'build.dir' in feature.debug"));
+
+    let content = read_config(&temp_dir);
+    assert!(!content.contains("build.dir"));
+}
+
+// ===== Tests for --del --index =====
+
+#[test]
+fn test_del_by_index() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.flags", "-O2", "-Wall", "-O2"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--del", "--index", "0", "build.flags"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.flags"])
+        .assert()
+        .success()
+        .stdout("-Wall\n-O2\n");
+}
+
+#[test]
+fn test_del_by_index_out_of_range() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.flags", "-O2", "-Wall"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--del", "--index", "2", "build.flags"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("index 2 is out of range for 'build.flags' (length 2)"));
+}
+
+// ===== Tests for --replace =====
+
+#[test]
+fn test_replace_keeps_position() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.options", "-O2", "-lfoo1", "-Wall"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--replace", "build.options", "-lfoo1", "-lfoo2"])
+        .assert()
+        .success()
+        .stderr("Replaced 1 element(s)\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.options"])
+        .assert()
+        .success()
+        .stdout("-O2\n-lfoo2\n-Wall\n");
+}
+
+#[test]
+fn test_del_and_replace_typed_elements() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--add", "--type", "int", "ports", "8080", "8443", "9000"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--del", "--type", "int", "ports", "8080"])
+        .assert()
+        .success()
+        .stderr("Removed 1 value(s)\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--replace", "ports", "8443", "8444"])
+        .assert()
+        .success()
+        .stderr("Replaced 1 element(s)\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--list", "ports"])
+        .assert()
+        .success()
+        .stdout("8444\n9000\n");
+    assert!(read_config(&temp_dir).contains("ports = [8444, 9000]\n"));
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--list", "--raw", "ports"])
+        .assert()
+        .success()
+        .stdout("[8444, 9000]\n");
+
+    // The replacement keeps the element's type
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--replace", "ports", "9000", "http"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("'http' is not a valid int"));
+}
+
+#[test]
+fn test_del_matches_element_type() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        "[global]\n\n[model]\nports = [8080, \"8080\", 9000]\n",
+    )
+    .unwrap();
+
+    // Like --add --unique, the integer 8080 and the string "8080" are different values
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--del", "ports", "8080"])
+        .assert()
+        .success()
+        .stderr("Removed 1 value(s)\n");
+    assert!(read_config(&temp_dir).contains("ports = [8080, 9000]\n"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--del", "--infer", "ports", "8080"])
+        .assert()
+        .success()
+        .stderr("Removed 1 value(s)\n");
+    assert!(read_config(&temp_dir).contains("ports = [9000]\n"));
+}
+
+#[test]
+fn test_replace_missing_key() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--replace", "build.options", "a", "b"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_add_converts_typed_scalar_to_array() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--type", "int", "jobs", "4"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--add", "jobs", "8"])
+        .assert()
+        .success();
+
+    assert!(read_config(&temp_dir).contains("jobs = [4, \"8\"]"));
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "jobs"])
+        .assert()
+        .success()
+        .stdout("4\n8\n");
+}
+
+// ===== Tests for --section =====
+
+#[test]
+fn test_custom_section() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--section", "cache", "--set", "dir", "/tmp/cache"])
+        .assert()
+        .success();
+
+    assert!(read_config(&temp_dir).contains("\n[cache]\ndir = \"/tmp/cache\"\n"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--section", "cache", "--list"])
+        .assert()
+        .success()
+        .stdout("dir = /tmp/cache\n");
+}
+
+#[test]
+fn test_custom_section_missing() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--section", "cache", "--list"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("section 'cache' not found"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--section", "cache", "--global", "--list"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_section_rejects_feature_container() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--section", "feature", "--set", "dir", "build"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("'feature' holds the feature sections"));
+    assert_eq!(read_config(&temp_dir), before);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--section", "cache", "--set", "dir", "/tmp/cache"])
+        .assert()
+        .success()
+        .stderr("");
+}
+
+#[test]
+fn test_section_validates_feature_names() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    for args in [
+        vec!["--section", "feature.My Feature", "--ensure"],
+        vec!["--section", "feature.x y", "--set", "a", "b"],
+    ] {
+        get_cmd(&temp_dir)
+            .arg("config")
+            .args(&args)
+            .assert()
+            .code(5)
+            .stderr(predicate::str::contains("invalid feature name"));
+    }
+    assert_eq!(read_config(&temp_dir), before);
+
+    // A valid feature name is normalized the same way --feature does
+    get_cmd(&temp_dir)
+        .args(["config", "--section", "feature.Debug", "--ensure"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--exists"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_section_validates_other_names() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    for section in ["Weird Name", "Cache", "a.b"] {
+        get_cmd(&temp_dir)
+            .args(["config", "--section", section, "--set", "dir", "build"])
+            .assert()
+            .code(5)
+            .stderr(predicate::str::contains("invalid section name"));
+    }
+    assert_eq!(read_config(&temp_dir), before);
+}
+
+#[test]
+fn test_section_warns_about_feature_keys_in_global() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--section", "global", "--set", "build.cmd", "make"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Warning: 'build.cmd' is a feature key but targets [global]; use --make to configure a feature",
+        ));
+    get_cmd(&temp_dir)
+        .args(["config", "--section", "global", "--set", "compiler", "gcc"])
+        .assert()
+        .success()
+        .stderr("");
+}
+
+// ===== Tests for --show-path =====
+
+#[test]
+fn test_show_path() {
+    let temp_dir = setup_test_env();
+    let sub_dir = temp_dir.path().join("src/nested");
+    fs::create_dir_all(&sub_dir).unwrap();
+    let config_path = temp_dir.path().join(".c2rust/config.toml");
+
+    let mut cmd = Command::cargo_bin("c2rust-config").unwrap();
+    cmd.current_dir(&sub_dir)
+        .args(["config", "--show-path"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", config_path.display()));
+
+    let mut cmd = Command::cargo_bin("c2rust-config").unwrap();
+    cmd.current_dir(&sub_dir)
+        .args(["config", "--global", "--show-path", "--set", "compiler", "gcc"])
+        .assert()
+        .success()
+        .stderr(format!("Using {}\n", config_path.display()));
+}
+
+// ===== Tests for merge-feature =====
+
+/// Helper that writes a base feature and a partially configured release feature
+fn setup_merge_env() -> TempDir {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        r#"[global]
+
+[model]
+
+[feature.default]
+"build.dir" = "build"
+"build.cmd" = "make"
+"build.options" = ["-O0", "-g"]
+
+[feature.release]
+"build.cmd" = "make release"
+"build.options" = ["-O2"]
+"#,
+    )
+    .unwrap();
+    temp_dir
+}
+
+#[test]
+fn test_merge_feature_keeps_target_keys() {
+    let temp_dir = setup_merge_env();
+
+    get_cmd(&temp_dir)
+        .args(["merge-feature", "default", "release"])
+        .assert()
+        .success()
+        .stdout("Merged 1 key(s) from feature.default into feature.release\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--list"])
+        .assert()
+        .success()
+        .stdout("build.cmd = make release\nbuild.options = -O2\nbuild.dir = build\n");
+}
+
+#[test]
+fn test_merge_feature_overwrite_and_append() {
+    let temp_dir = setup_merge_env();
+
+    get_cmd(&temp_dir)
+        .args(["merge-feature", "default", "release", "--append-arrays"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--list", "build.options"])
+        .assert()
+        .success()
+        .stdout("-O0\n-g\n-O2\n");
+
+    get_cmd(&temp_dir)
+        .args(["merge-feature", "default", "release", "--overwrite"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--list"])
+        .assert()
+        .success()
+        .stdout("build.cmd = make\nbuild.options = [\n  -O0\n  -g\n]\nbuild.dir = build\n");
+}
+
+#[test]
+fn test_merge_feature_missing_base() {
+    let temp_dir = setup_merge_env();
+
+    get_cmd(&temp_dir)
+        .args(["merge-feature", "nope", "release"])
+        .assert()
+        .code(3);
+}
+
+// ===== Tests for rename-feature =====
+
+#[test]
+fn test_rename_feature() {
+    let temp_dir = setup_merge_env();
+
+    get_cmd(&temp_dir)
+        .args(["rename-feature", "default", "debug"])
+        .assert()
+        .success()
+        .stdout("Renamed feature.default to feature.debug\n");
+
+    // The section keeps its place ahead of release
+    get_cmd(&temp_dir)
+        .args(["list-features"])
+        .assert()
+        .success()
+        .stdout("debug\nrelease\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--list", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("make\n");
+}
+
+#[test]
+fn test_rename_feature_collision_requires_merge() {
+    let temp_dir = setup_merge_env();
+    let before = read_config(&temp_dir);
+
+    get_cmd(&temp_dir)
+        .args(["rename-feature", "default", "release"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("pass --merge"));
+    assert_eq!(read_config(&temp_dir), before);
+}
+
+#[test]
+fn test_rename_feature_merge() {
+    let temp_dir = setup_merge_env();
+
+    get_cmd(&temp_dir)
+        .args(["rename-feature", "default", "release", "--merge"])
+        .assert()
+        .success()
+        .stdout("Merged 1 key(s) from feature.default into feature.release and removed feature.default\n");
+
+    // Keys release already had win; the rest come from default
+    get_cmd(&temp_dir)
+        .args(["list-features"])
+        .assert()
+        .success()
+        .stdout("release\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--list"])
+        .assert()
+        .success()
+        .stdout("build.cmd = make release\nbuild.options = -O2\nbuild.dir = build\n");
+}
+
+#[test]
+fn test_rename_feature_merge_append_arrays() {
+    let temp_dir = setup_merge_env();
+
+    get_cmd(&temp_dir)
+        .args(["rename-feature", "default", "release", "--merge", "--append-arrays"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--list", "build.options"])
+        .assert()
+        .success()
+        .stdout("-O0\n-g\n-O2\n");
+}
+
+// ===== Tests for --types =====
+
+#[test]
+fn test_list_types() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        "[global]\n\n[model]\nname = \"gcc\"\nport = 8080\nstream = true\nstop = [\"a\", \"b\"]\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--list", "--types"])
+        .assert()
+        .success()
+        .stdout("name = gcc (string)\nport = 8080 (integer)\nstream = true (boolean)\nstop = [\n  a\n  b\n] (array)\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(").not());
+}
+
+// ===== Tests for --comment =====
+
+#[test]
+fn test_set_with_comment() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "jobs", "4"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--comment", "C compiler used for the build", "compiler", "gcc"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("jobs = \"4\"\n# C compiler used for the build\ncompiler = [\"gcc\"]\n"));
+
+    // A new comment replaces the old one
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--comment", "Preferred compiler", "compiler", "clang"])
+        .assert()
+        .success();
+    let content = read_config(&temp_dir);
+    assert!(content.contains("jobs = \"4\"\n# Preferred compiler\ncompiler = [\"clang\"]\n"));
+    assert!(!content.contains("C compiler used for the build"));
+}
+
+// ===== Tests for --create =====
+
+#[test]
+fn test_set_into_new_feature_requires_create() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debgu", "--set", "build.cmd", "make"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("feature 'feature.debgu' does not exist yet; pass --create to create it"));
+    assert!(!read_config(&temp_dir).contains("debgu"));
+
+    // The hint is part of the error, so it survives --quiet and JSON errors
+    let output = get_cmd(&temp_dir)
+        .args(["config", "--error-format", "json", "--make", "--feature", "debgu", "--quiet", "--set", "build.cmd", "make"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["kind"], "FeatureNotFound");
+    assert!(error["message"].as_str().unwrap().contains("pass --create to create it"), "{}", error);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--create", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("[feature.debug]"));
+
+    // Existing features and feature.default need no --create
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--add", "build.files.0", "main.c"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_create_near_duplicate_feature_warns() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--create", "--set", "build.cmd", "make"])
+        .assert()
+        .success()
+        .stderr("");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debg", "--create", "--set", "build.cmd", "make"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("did you mean 'debug'?"));
+    assert!(read_config(&temp_dir).contains("[feature.debg]"));
+}
+
+#[test]
+fn test_create_short_feature_does_not_warn() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "a", "--create", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    // One edit apart, but too short for that to suggest a typo
+    for name in ["b", "ab"] {
+        get_cmd(&temp_dir)
+            .args(["config", "--make", "--feature", name, "--create", "--set", "build.cmd", "make"])
+            .assert()
+            .success()
+            .stderr("");
+    }
+}
+
+// ===== Tests for --list --recursive =====
+
+#[test]
+fn test_list_recursive() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "gcc"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "model_name", "gpt-4"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.options", "-O2", "-g"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--list", "--recursive"])
+        .assert()
+        .success()
+        .stdout(
+            "global.compiler = gcc\n\
+             model.model_name = gpt-4\n\
+             feature.default.build.options = [\n  -O2\n  -g\n]\n",
+        );
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "--recursive"])
+        .assert()
+        .code(5);
+}
+
+// ===== Tests for the [schema] Section =====
+
+/// Helper that writes a config restricting global and feature keys
+fn setup_schema_env() -> TempDir {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        r#"[global]
+
+[model]
+
+[schema]
+global = ["compiler"]
+feature = ["build.*", "clean.*", "test.*"]
+"#,
+    )
+    .unwrap();
+    temp_dir
+}
+
+#[test]
+fn test_schema_allows_known_keys() {
+    let temp_dir = setup_schema_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "gcc"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "build.files.0", "main.c"])
+        .assert()
+        .success();
+    // model has no schema entry, so any key is allowed
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "api_key", "x"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_schema_rejects_unknown_keys() {
+    let temp_dir = setup_schema_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "biuld.cmd", "make"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("key 'biuld.cmd' is not allowed in 'feature.default'"));
+    assert!(!read_config(&temp_dir).contains("biuld"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--force", "--set", "linker", "lld"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("linker = \"lld\""));
+}
+
+// ===== Tests for --ensure =====
+
+#[test]
+fn test_ensure_creates_empty_feature() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "ci", "--ensure"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("[feature.ci]"));
+
+    get_cmd(&temp_dir)
+        .args(["list-features"])
+        .assert()
+        .success()
+        .stdout("ci\n");
+
+    // Running it again is a no-op, and the section is ready for --add
+    let before = read_config(&temp_dir);
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "ci", "--ensure"])
+        .assert()
+        .success();
+    assert_eq!(read_config(&temp_dir), before);
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "ci", "--add", "build.files.0", "main.c"])
+        .assert()
+        .success();
+}
+
+// ===== Tests for --error-format json =====
+
+#[test]
+fn test_error_format_json() {
+    let temp_dir = setup_test_env();
+
+    let output = get_cmd(&temp_dir)
+        .args(["config", "--error-format", "json", "--global", "--list", "missing"])
+        .assert()
+        .code(2)
+        .get_output()
+        .stderr
+        .clone();
+
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(json["kind"], "KeyNotFound");
+    assert_eq!(json["code"], 2);
+    assert_eq!(json["message"], "Error: key 'missing' not found");
+}
+
+// ===== Tests for Colored Output =====
+
+#[test]
+fn test_no_color_when_piped_or_disabled() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        "[global]\n\n[model]\n\n[feature.default]\n\"build.dir\" = \"build\"\n",
+    )
+    .unwrap();
+
+    // Piped stderr: warnings and errors stay plain
+    get_cmd(&temp_dir)
+        .args(["validate"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("Warning: Feature 'feature.default'"))
+        .stderr(predicate::str::contains("\x1b[").not());
+
+    get_cmd(&temp_dir)
+        .env("NO_COLOR", "1")
+        .args(["config", "--global", "--list", "missing"])
+        .assert()
+        .code(2)
+        .stderr("Error: key 'missing' not found\n");
+}
+
+// ===== Tests for --nested =====
+
+#[test]
+fn test_list_nested_inline_table() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(
+        &config_path,
+        "[global]\n\n[model]\n\n[feature.default]\nbuild.cmd = \"make\"\nbuild.options = [{ name = \"debug\", flags = [\"-g\", \"-O0\"] }, \"-Wall\"]\n",
+    )
+    .unwrap();
+
+    // The default flat listing prints each element on one line
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.options"])
+        .assert()
+        .success()
+        .stdout("{ name = \"debug\", flags = [\"-g\", \"-O0\"] }\n-Wall\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--nested", "build.options"])
+        .assert()
+        .success()
+        .stdout("{\n  name = debug\n  flags = [\n    -g\n    -O0\n  ]\n}\n-Wall\n");
+
+    // Keys without tables keep the usual form in a whole-section listing
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--nested"])
+        .assert()
+        .success()
+        .stdout("build.cmd = make\nbuild.options = [\n  {\n    name = debug\n    flags = [\n      -g\n      -O0\n    ]\n  }\n  -Wall\n]\n");
+}
+
+// ===== Tests for --keys-only / --values-only =====
+
+#[test]
+fn test_list_keys_only_and_values_only() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.options", "-O2", "-g"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--keys-only"])
+        .assert()
+        .success()
+        .stdout("build.cmd\nbuild.options\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--values-only"])
+        .assert()
+        .success()
+        .stdout("make\n-O2\n-g\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "--keys-only", "build.cmd"])
+        .assert()
+        .code(5);
+}
+
+// ===== Tests for --set --kv =====
+
+#[test]
+fn test_set_kv_pairs() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "--kv", "build.dir=build", "build.cmd=make", "test.cmd=make CHECK=a=b"])
+        .assert()
+        .success();
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("build.dir = \"build\"\nbuild.cmd = \"make\"\ntest.cmd = \"make CHECK=a=b\"\n"), "{}", content);
+}
+
+#[test]
+fn test_set_kv_writes_all_or_nothing() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    // The pairs share one load/save cycle, so a bad pair leaves the file untouched
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "--kv", "build.dir=build", "build.cmd"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--kv expects key=value pairs, got 'build.cmd'"));
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "--kv", "--type", "int", "port=8080", "retries=many"])
+        .assert()
+        .failure();
+    assert_eq!(read_config(&temp_dir), before);
+}
+
+// ===== Tests for --set --json =====
+
+#[test]
+fn test_set_json_inline_table() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--json", r#"{"cc": "gcc", "cxx": "g++"}"#, "compiler"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("compiler = { cc = \"gcc\", cxx = \"g++\" }"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "compiler"])
+        .assert()
+        .success()
+        .stdout("{ cc = \"gcc\", cxx = \"g++\" }\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--exists", "compiler"])
+        .assert()
+        .success();
+
+    // A later save writes the table as dotted keys, which still read back as one value
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "jobs", "4"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "compiler"])
+        .assert()
+        .success()
+        .stdout("{ cc = \"gcc\", cxx = \"g++\" }\n");
+
+    let output = get_cmd(&temp_dir).args(["export"]).assert().success().get_output().stdout.clone();
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(json["global"]["compiler"], serde_json::json!({"cc": "gcc", "cxx": "g++"}));
+}
+
+#[test]
+fn test_unset_reloaded_inline_table() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        "[global]\ncompiler = { cc = \"gcc\", cxx = \"g++\" }\njobs = \"4\"\n\n[model]\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--unset", "compiler"])
+        .assert()
+        .success();
+    assert_eq!(read_config(&temp_dir), "[global]\njobs = \"4\"\n\n[model]\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--exists", "compiler"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_reset_reloaded_inline_table() {
+    let temp_dir = setup_test_env();
+    fs::write(
+        temp_dir.path().join(".c2rust/config.toml"),
+        "[global]\ncompiler = { cc = \"gcc\", cxx = \"g++\" }\njobs = \"4\"\n\n[model]\n",
+    )
+    .unwrap();
+
+    // A new table replaces every old sub-key instead of merging with them
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--json", r#"{"cc": "clang"}"#, "compiler"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("separate quoted keys").not());
+    assert_eq!(
+        read_config(&temp_dir),
+        "[global]\ncompiler = { cc = \"clang\" }\njobs = \"4\"\n\n[model]\n"
+    );
+
+    // A scalar replaces the table in place and reports the type change
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "clang"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("changed type from inline table to array"));
+    assert_eq!(
+        read_config(&temp_dir),
+        "[global]\ncompiler = [\"clang\"]\njobs = \"4\"\n\n[model]\n"
+    );
+}
+
+#[test]
+fn test_set_json_invalid() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--json", "{cc: gcc}", "compiler"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("invalid JSON"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "--json", "8080", "port"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("port = 8080\n"));
+}
+
+// ===== Tests for --exists =====
+
+#[test]
+fn test_exists_feature_and_key() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--create", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--exists"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--exists"])
+        .assert()
+        .code(3)
+        .stdout("")
+        .stderr("");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--exists", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--exists", "build.dir"])
+        .assert()
+        .code(2)
+        .stderr("");
+}
+
+#[test]
+fn test_set_warns_on_type_change() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "--type", "int", "port", "8080"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "port", "abc"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Warning: 'port' changed type from integer to string"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "--quiet", "--type", "int", "port", "80"])
+        .assert()
+        .success()
+        .stderr("");
+
+    // Same type: no warning
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "--type", "int", "port", "81"])
+        .assert()
+        .success()
+        .stderr("");
+}
+
+// ===== Tests for --trim =====
+
+#[test]
+fn test_add_with_and_without_trim() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--add", "compiler", " gcc "])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("compiler = [\" gcc \"]"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--add", "--trim", "padded", " gcc "])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("padded = [\"gcc\"]"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--trim", "linker", "  lld\t"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("linker = \"lld\""));
+}
+
+#[test]
+fn test_del_reports_removed_count() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.flags", "-O2", "-Wall", "-O2"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--del", "build.flags", "-O2"])
+        .assert()
+        .success()
+        .stderr("Removed 2 value(s)\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--del", "build.flags", "-g"])
+        .assert()
+        .success()
+        .stderr("No matching values to remove\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--del", "--quiet", "build.flags", "-Wall"])
+        .assert()
+        .success()
+        .stderr("");
+}
+
+// ===== Tests for --from-file =====
+
+#[test]
+fn test_set_from_file() {
+    let temp_dir = setup_test_env();
+    let value_path = temp_dir.path().join("defines.txt");
+    fs::write(&value_path, "-DVERSION=\"1.2\" -DLONG_NAME=1\n").unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "--from-file", value_path.to_str().unwrap(), "build.defines"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.defines"])
+        .assert()
+        .success()
+        .stdout("-DVERSION=\"1.2\" -DLONG_NAME=1\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "--from-file", value_path.to_str().unwrap(), "build.defines", "extra"])
+        .assert()
+        .code(5);
+}
+
+// ===== Tests for --split =====
+
+#[test]
+fn test_add_and_del_split_values() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "--split", ",", "build.files.0", "main.c,debug.c,,common.c"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.files.0"])
+        .assert()
+        .success()
+        .stdout("main.c\ndebug.c\ncommon.c\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--del", "--split", ",", "build.files.0", "main.c,common.c"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.files.0"])
+        .assert()
+        .success()
+        .stdout("debug.c\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "--split", ",", "build.cmd", "a,b"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--split only applies to --add and --del"));
+}
+
+// ===== Tests for --stdin =====
+
+#[test]
+fn test_set_from_stdin() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--stdin", "compiler"])
+        .write_stdin("gcc\n")
+        .assert()
+        .success();
+
+    assert!(read_config(&temp_dir).contains("compiler = [\"gcc\"]\n"));
+}
+
+#[test]
+fn test_add_from_stdin_lines() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--add", "--stdin", "build.files.0"])
+        .write_stdin("main.c\nutil.c\n\nio.c\n")
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.files.0"])
+        .assert()
+        .success()
+        .stdout("main.c\nutil.c\nio.c\n");
+}
+
+// ===== Tests for feature name validation =====
+
+#[test]
+fn test_invalid_feature_name_rejected() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--create", "--feature", "my feature", "--set", "build.cmd", "make"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("invalid feature name 'my feature'"));
+
+    assert_eq!(read_config(&temp_dir), before);
+}
+
+#[test]
+fn test_feature_named_all_rejected() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    for args in [
+        vec!["config", "--section", "feature.ALL", "--set", "build.cmd", "make"],
+        vec!["config", "--section", "feature.all", "--ensure"],
+        vec!["rename-feature", "default", "all"],
+    ] {
+        get_cmd(&temp_dir)
+            .args(&args)
+            .assert()
+            .code(5)
+            .stderr(predicate::str::contains("invalid feature name 'all'"));
+    }
+    assert_eq!(read_config(&temp_dir), before);
+
+    // A default feature named `all` is refused when --make uses it
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "default_feature", "all"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("invalid feature name 'all'"));
+    assert!(!read_config(&temp_dir).contains("[feature.all]"));
+}
+
+#[test]
+fn test_feature_name_with_dash_accepted() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--create", "--feature", "My-Feature", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    assert!(read_config(&temp_dir).contains("[feature.my-feature]"));
+}
+
+// ===== Tests for --rename-value =====
+
+#[test]
+fn test_rename_value_across_arrays() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.includes", "old/include", "src"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "clean.dirs", "old", "old/include"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "old/include"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--rename-value", "old/include", "new/include"])
+        .assert()
+        .success()
+        .stderr("  build.includes: 1 element(s)\n  clean.dirs: 1 element(s)\nReplaced 2 element(s) across 2 key(s)\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.includes"])
+        .assert()
+        .success()
+        .stdout("new/include\nsrc\n");
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "clean.dirs"])
+        .assert()
+        .success()
+        .stdout("old\nnew/include\n");
+    // Scalars are left alone
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.dir"])
+        .assert()
+        .success()
+        .stdout("old/include\n");
+}
+
+#[test]
+fn test_rename_value_requires_two_values() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--rename-value", "old"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--rename-value requires exactly two values"));
+}
+
+// ===== Tests for multiple --feature values =====
+
+#[test]
+fn test_set_across_multiple_features() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--create", "--feature", "debug", "--feature", "release", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("[feature.debug]"));
+    assert!(content.contains("[feature.release]"));
+    assert_eq!(content.matches("\nbuild.cmd = \"make\"").count(), 2, "{}", content);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug,release", "--list"])
+        .assert()
+        .success()
+        .stdout("[feature.debug]\nbuild.cmd = make\n\n[feature.release]\nbuild.cmd = make\n");
+}
+
+#[test]
+fn test_multiple_features_abort_before_writing() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    // release does not exist and --create is absent, so nothing is written for debug either
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "default,release", "--set", "build.cmd", "make"])
+        .assert()
+        .code(3);
+
+    assert_eq!(read_config(&temp_dir), before);
+}
+
+#[test]
+fn test_list_key_across_all_features() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--create", "--set", "build.cmd", "make release"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "docs", "--create", "--set", "build.dir", "docs"])
+        .assert()
+        .success();
+
+    // docs has no build.cmd, so it is skipped with a note
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "all", "--list", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("[feature.default]\nmake\n\n[feature.release]\nmake release\n")
+        .stderr(predicate::str::contains("Note: 'build.cmd' is not set in feature.docs"));
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "all", "--list", "test.cmd"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_feature_all_rejects_mutations() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "all", "--set", "build.cmd", "make"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--feature all only applies to read operations"));
+    assert_eq!(read_config(&temp_dir), before);
+}
+
+// ===== Tests for [global] default_feature =====
+
+#[test]
+fn test_make_uses_default_feature_from_global() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "release", "--create", "--set", "build.cmd", "make release"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "default_feature", "release"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("make release\n");
+
+    // An explicit --feature still wins
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "default", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "default", "--list", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("make\n");
+}
+
+#[test]
+fn test_make_without_default_feature_uses_default() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    assert!(read_config(&temp_dir).contains("[feature.default]\nbuild.cmd = \"make\""));
+}
+
+// ===== Tests for --log =====
+
+#[test]
+fn test_log_records_mutations() {
+    let temp_dir = setup_test_env();
+    let log_path = temp_dir.path().join("audit.log");
+    let log = log_path.to_str().unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--log", log, "--set", "compiler", "gcc"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--log", log, "--set", "build.cmd", "make", "all"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--log", log, "--list"])
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&log_path).unwrap();
+    let lines: Vec<serde_json::Value> = content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 2, "{}", content);
+    assert_eq!(lines[0]["mode"], "global");
+    assert_eq!(lines[0]["section"], "global");
+    assert_eq!(lines[0]["key"], "compiler");
+    assert_eq!(lines[0]["operation"], "set");
+    assert_eq!(lines[0]["values"], serde_json::json!(["gcc"]));
+    assert_eq!(lines[1]["mode"], "make");
+    assert_eq!(lines[1]["section"], "feature.default");
+    assert_eq!(lines[1]["key"], "build.cmd");
+    assert_eq!(lines[1]["values"], serde_json::json!(["make", "all"]));
+    assert!(lines[1]["time"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn test_log_feature_wildcard_unset_records_changed_features_only() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(
+        &config_path,
+        "[global]\n\n[model]\n\n\
+         [feature.a]\nbuild.options = \"-O2\"\n\n\
+         [feature.b]\nbuild.cmd = \"make\"\n",
+    )
+    .unwrap();
+    let log_path = temp_dir.path().join("audit.log");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--log", log_path.to_str().unwrap(), "--feature", "all", "--unset", "build.options"])
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&log_path).unwrap();
+    let lines: Vec<serde_json::Value> = content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 1, "{}", content);
+    assert_eq!(lines[0]["section"], "feature.a");
+    assert_eq!(lines[0]["operation"], "unset");
+}
+
+// ===== Tests for pattern listing =====
+
+#[test]
+fn test_list_prefix_pattern() {
+    let temp_dir = setup_test_env();
+
+    for (key, value) in [("build.dir", "build"), ("clean.cmd", "make clean"), ("build.cmd", "make")] {
+        get_cmd(&temp_dir)
+            .args(["config", "--make", "--set", key, value])
+            .assert()
+            .success();
+    }
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.options", "-O2", "-g"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.*"])
+        .assert()
+        .success()
+        .stdout("build.dir = build\nbuild.cmd = make\nbuild.options = [\n  -O2\n  -g\n]\n");
+
+    // An exact key still prints only its values
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "build.cmd"])
+        .assert()
+        .success()
+        .stdout("make\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list", "test.*"])
+        .assert()
+        .code(2);
+}
+
+// ===== Tests for --unset --prefix =====
+
+#[test]
+fn test_unset_prefix_removes_nested_keys() {
+    let temp_dir = setup_test_env();
+
+    for (key, value) in [("build.dir", "build"), ("build.cmd", "make"), ("builder", "ninja"), ("clean.cmd", "make clean")] {
+        get_cmd(&temp_dir)
+            .args(["config", "--make", "--set", key, value])
+            .assert()
+            .success();
+    }
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--unset", "--prefix", "build"])
+        .assert()
+        .success()
+        .stderr("Removed 2 key(s)\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list"])
+        .assert()
+        .success()
+        .stdout("builder = ninja\nclean.cmd = make clean\n");
+}
+
+#[test]
+fn test_unset_prefix_requires_key() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+
+    // Unlike a bare --make --unset, this must not remove the whole feature
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--unset", "--prefix"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--unset requires a key"));
+
+    assert!(read_config(&temp_dir).contains("[feature.default]"));
+}
+
+// ===== Tests for --canonical =====
+
+#[test]
+fn test_canonical_is_independent_of_insertion_order() {
+    let first = setup_test_env();
+    let second = setup_test_env();
+
+    let keys = [("build.dir", "build"), ("clean.cmd", "make clean"), ("build.cmd", "make")];
+    for (key, value) in keys {
+        get_cmd(&first).args(["config", "--make", "--set", key, value]).assert().success();
+    }
+    for (key, value) in keys.iter().rev() {
+        get_cmd(&second).args(["config", "--make", "--set", key, value]).assert().success();
+    }
+    get_cmd(&first)
+        .args(["config", "--make", "--set", "--comment", "output directory", "build.dir", "build"])
+        .assert()
+        .success();
+    get_cmd(&second)
+        .args(["config", "--make", "--set", "--comment", "output directory", "build.dir", "build"])
+        .assert()
+        .success();
+    assert_ne!(read_config(&first), read_config(&second));
+
+    for temp_dir in [&first, &second] {
+        get_cmd(temp_dir).args(["config", "--make", "--canonical"]).assert().success();
+    }
+
+    let canonical = read_config(&first);
+    assert_eq!(canonical, read_config(&second));
+    assert!(canonical.contains(
+        "build.cmd = \"make\"\n# output directory\nbuild.dir = \"build\"\nclean.cmd = \"make clean\"\n"
+    ), "{}", canonical);
+
+    // Running it again changes nothing
+    get_cmd(&first).args(["config", "--make", "--canonical"]).assert().success();
+    assert_eq!(read_config(&first), canonical);
+}
+
+// ===== Tests for format =====
+
+#[test]
+fn test_format_tidies_and_is_idempotent() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(
+        &config_path,
+        "# top comment\n[global]   \ncompiler=\"gcc\"    # cc\n\n\n\n  port =   8080\n\n\n[model]\n\n\n\
+         [feature.default]\n  \"build.options\" = [ \"-O2\",\n        # debug\n   \"-g\"  ,\n  ]\n\n\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir).args(["format"]).assert().success();
+    let formatted = read_config(&temp_dir);
+    assert_eq!(
+        formatted,
+        "# top comment\n[global]\ncompiler = \"gcc\" # cc\n\nport = 8080\n\n[model]\n\n\
+         [feature.default]\nbuild.options = [\n    \"-O2\",\n    # debug\n    \"-g\",\n]\n"
+    );
+
+    get_cmd(&temp_dir).args(["format"]).assert().success();
+    assert_eq!(read_config(&temp_dir), formatted);
+}
+
+// ===== Tests for parse error context =====
+
+#[test]
+fn test_parse_error_names_path_and_line() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(&config_path, "[global]\ncompiler = \"gcc\n\n[model]\n").unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list"])
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains(format!("{}:2:16: invalid basic string", config_path.display())))
+        .stderr(predicate::str::contains("2 | compiler = \"gcc\n"));
+}
+
+// ===== Tests for --if-absent =====
+
+#[test]
+fn test_set_if_absent_keeps_existing_value() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--if-absent", "compiler", "gcc"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--if-absent", "compiler", "clang"])
+        .assert()
+        .success()
+        .stderr("Note: 'compiler' already exists in global, leaving it unchanged\n");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "compiler"])
+        .assert()
+        .success()
+        .stdout("gcc\n");
+}
+
+// ===== Tests for --expect-current =====
+
+#[test]
+fn test_set_expect_current_matching() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "gcc"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--expect-current", "gcc", "compiler", "clang"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "compiler"])
+        .assert()
+        .success()
+        .stdout("clang\n");
+}
+
+#[test]
+fn test_set_expect_current_mismatch_does_not_write() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "gcc"])
+        .assert()
+        .success();
+    let before = read_config(&temp_dir);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--expect-current", "icc", "compiler", "clang"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("expected 'compiler' to be 'icc' but it is 'gcc'"));
+    assert_eq!(read_config(&temp_dir), before);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--expect-current", "x", "linker", "ld"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("it is not set"));
+    assert_eq!(read_config(&temp_dir), before);
+}
+
+// ===== Tests for --output =====
+
+#[test]
+fn test_list_to_output_file() {
+    let temp_dir = setup_test_env();
+    let output_path = temp_dir.path().join("reports").join("default.txt");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "build"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--output", output_path.to_str().unwrap(), "--list"])
+        .assert()
+        .success()
+        .stdout("");
+
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), "build.cmd = make\nbuild.dir = build\n");
+}
+
+#[test]
+fn test_output_redirects_mutations() {
+    let temp_dir = setup_test_env();
+    let output_path = temp_dir.path().join("variants").join("other.toml");
+    let before = read_config(&temp_dir);
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--output", output_path.to_str().unwrap(), "--set", "compiler", "clang"])
+        .assert()
+        .success()
+        .stdout("");
+
+    // config.toml is untouched; the copy has the change
+    assert_eq!(read_config(&temp_dir), before);
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "[global]\ncompiler = [\"clang\"]\n\n[model]\n"
+    );
+}
+
+#[test]
+fn test_output_rejected_for_exists() {
+    let temp_dir = setup_test_env();
+    let output_path = temp_dir.path().join("out.txt");
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--output", output_path.to_str().unwrap(), "--exists", "compiler"])
+        .assert()
+        .failure();
+    assert!(!output_path.exists());
+}
+
+// ===== Tests for --watch =====
+
+#[test]
+#[cfg(not(feature = "watch"))]
+fn test_watch_requires_feature() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--watch", "--list"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("built with the `watch` feature"));
+}
+
+#[test]
+#[cfg(feature = "watch")]
+fn test_watch_rerenders_on_change() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let temp_dir = setup_test_env();
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "gcc"])
+        .assert()
+        .success();
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_c2rust-config"))
+        .args(["config", "--global", "--watch", "--list"])
+        .current_dir(temp_dir.path())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if sender.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let first = receiver.recv_timeout(Duration::from_secs(10));
+    // Give the watcher a moment to start before changing the file
+    std::thread::sleep(Duration::from_millis(500));
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "clang"])
+        .assert()
+        .success();
+    let second = receiver.recv_timeout(Duration::from_secs(10));
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert_eq!(first.unwrap(), "compiler = gcc");
+    assert_eq!(second.unwrap(), "compiler = clang");
+}
+
+// ===== Tests for unsetting the last key =====
+
+#[test]
+fn test_unset_last_key_keeps_section_header() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(
+        &config_path,
+        "[global]\n\n[model]\n\n# debug build\n[feature.debug]\n# the command\n\"build.cmd\" = \"make\" # trailing\n\n[feature.release]\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--unset", "build.cmd"])
+        .assert()
+        .success();
+
+    assert_eq!(
+        read_config(&temp_dir),
+        "[global]\n\n[model]\n\n# debug build\n[feature.debug]\n\n[feature.release]\n"
+    );
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "debug", "--exists"])
+        .assert()
+        .success();
+}
+
+// ===== Tests for duplicate validation =====
+
+#[test]
+fn test_validate_duplicate_build_files() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.files.0", "main.c", "util.c", "main.c"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.options", "-O2"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["validate"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains(
+            "Feature 'feature.default' has duplicate values in build.files.0: main.c",
+        ));
+}
+
+#[test]
+fn test_validate_schema_no_duplicates_rule() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(
+        &config_path,
+        "[global]\n\n[model]\n\n[schema]\n\"no-duplicates\" = [\"test.args\"]\n\n\
+         [feature.default]\n\"test.args\" = [\"-v\", \"-v\"]\n\"clean.args\" = [\"-f\", \"-f\"]\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["validate"])
+        .assert()
+        .code(7)
+        .stderr(
+            predicate::str::contains("duplicate values in test.args: -v")
+                .and(predicate::str::contains("clean.args").not()),
+        );
+}
+
+// ===== Tests for --force-array =====
+
+#[test]
+fn test_set_force_array_single_value() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "--force-array", "compiler", "gcc"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--model", "--set", "--force-array", "--type", "int", "ports", "8080"])
+        .assert()
+        .success();
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("compiler = [\"gcc\"]\n"), "{}", content);
+    assert!(content.contains("ports = [8080]\n"), "{}", content);
+}
+
+// ===== Tests for the written form of dotted keys =====
+
+#[test]
+fn test_dotted_keys_written_bare() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(&config_path, "[global]\n\n[model]\n\n[feature.default]\n\"clean.cmd\" = \"make clean\"\n").unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "build"])
+        .assert()
+        .success();
+
+    // Keys read in quoted form are rewritten in the same bare form
+    assert_eq!(
+        read_config(&temp_dir),
+        "[global]\n\n[model]\n\n[feature.default]\nclean.cmd = \"make clean\"\nbuild.dir = \"build\"\n"
+    );
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--list"])
+        .assert()
+        .success()
+        .stdout("clean.cmd = make clean\nbuild.dir = build\n");
+}
+
+#[test]
+fn test_clashing_dotted_keys_stay_quoted() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "cache", "on"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "cache.dir", "/tmp/cache"])
+        .assert()
+        .success();
+
+    let content = read_config(&temp_dir);
+    assert!(content.contains("cache = \"on\"\n\"cache.dir\" = \"/tmp/cache\"\n"), "{}", content);
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--list", "cache.dir"])
+        .assert()
+        .success()
+        .stdout("/tmp/cache\n");
+}
+
+#[test]
+fn test_set_next_to_nested_table_normalizes() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(&config_path, "[global]\n\n[model]\n\n[feature.default.build]\ncmd = \"make\"\n").unwrap();
+
+    // The nested table is rewritten alongside the new key, leaving one representation of build
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "build"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Warning").not());
+    assert_eq!(
+        read_config(&temp_dir),
+        "[global]\n\n[model]\n\n[feature.default]\nbuild.cmd = \"make\"\nbuild.dir = \"build\"\n"
+    );
+}
+
+#[test]
+fn test_set_warns_about_shadowed_key() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build", "make"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "build"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Warning: 'build.dir' and 'build' both exist in feature.default; they are kept as separate quoted keys",
+        ));
+
+    // Overwriting an existing key does not repeat the warning
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.dir", "out"])
+        .assert()
+        .success()
+        .stderr("");
+}
+
+// ===== Tests for diff =====
+
+#[test]
+fn test_diff_backup_shows_changed_key() {
+    let temp_dir = setup_test_env();
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make"])
+        .assert()
+        .success();
+    let c2rust_dir = temp_dir.path().join(".c2rust");
+    fs::copy(c2rust_dir.join("config.toml"), c2rust_dir.join("config.toml.bak")).unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "build.cmd", "make -j4"])
+        .assert()
+        .success();
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "gcc", "clang"])
+        .assert()
+        .success();
+
+    get_cmd(&temp_dir)
+        .args(["diff", "--backup"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("~ feature.default.build.cmd: make -> make -j4"))
+        .stdout(predicate::str::contains("+ global.compiler = [gcc, clang]"));
+}
+
+#[test]
+fn test_diff_output_file() {
+    let temp_dir = setup_test_env();
+    let other = temp_dir.path().join("other.toml");
+    fs::write(&other, "[global]\n\n[model]\nport = \"8080\"\n").unwrap();
+    let output = temp_dir.path().join("reports").join("diff.txt");
+
+    get_cmd(&temp_dir)
+        .args(["diff", other.to_str().unwrap(), "--output", output.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout("");
+    assert_eq!(fs::read_to_string(&output).unwrap(), "- model.port = 8080\n");
+}
+
+#[test]
+fn test_diff_backup_requires_backup() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["diff", "--backup"])
+        .assert()
+        .failure()
+        .code(5)
+        .stderr(predicate::str::contains("no backup found"));
+}
+
+#[test]
+fn test_feature_wildcard_unsets_key_where_present() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(
+        &config_path,
+        "[global]\n\n[model]\n\n\
+         [feature.a]\nbuild.cmd = \"make\"\nbuild.options = \"-O2\"\n\n\
+         [feature.b]\nbuild.cmd = \"make\"\n\n\
+         [feature.c]\nbuild.cmd = \"make\"\nbuild.options = \"-O2\"\n",
+    )
+    .unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "*", "--unset", "build.options"])
+        .assert()
+        .success()
+        .stdout("Unset 'build.options' in feature.a\nUnset 'build.options' in feature.c\n")
+        .stderr(predicate::str::contains("Note: 'build.options' is not set in feature.b"));
+    let config = read_config(&temp_dir);
+    assert!(!config.contains("build.options"));
+    assert_eq!(config.matches("build.cmd = \"make\"").count(), 3);
+
+    // Nothing left to remove
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "*", "--unset", "build.options"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_feature_wildcard_rejects_writes() {
+    let temp_dir = setup_test_env();
+    let before = read_config(&temp_dir);
+
+    for op in ["--set", "--add"] {
+        get_cmd(&temp_dir)
+            .args(["config", "--make", "--feature", "*", op, "build.cmd", "make"])
+            .assert()
+            .code(5);
+    }
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--feature", "*", "--unset"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("--unset requires a key"));
+    assert_eq!(read_config(&temp_dir), before);
+}
+
+// ===== Tests for print-config-schema =====
+
+#[test]
+fn test_print_config_schema() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // Works without a .c2rust directory
+    get_cmd(&temp_dir)
+        .args(["print-config-schema"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("[feature.<name>]")
+                .and(predicate::str::contains("build.files.N"))
+                .and(predicate::str::contains("build.options"))
+                .and(predicate::str::contains("directory the clean command runs in")),
+        );
+
+    let output = get_cmd(&temp_dir)
+        .args(["print-config-schema", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let feature_keys = &schema["feature.<name>"]["keys"];
+    assert!(feature_keys["build.files.N"].is_string());
+    assert!(feature_keys["build.options"].is_string());
+    assert_eq!(schema["global"]["description"], "settings shared by every feature");
+}
+
+// ===== Tests for array conventions =====
+
+#[test]
+fn test_set_global_compiler_stores_array() {
+    let temp_dir = setup_test_env();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--global", "--set", "compiler", "gcc"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("compiler = [\"gcc\"]\n"));
+
+    // Other keys, and compiler outside [global], keep the scalar default
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--set", "compiler", "gcc"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("[feature.default]\ncompiler = \"gcc\"\n"));
+}
+
+#[test]
+fn test_schema_always_array_rule() {
+    let temp_dir = setup_test_env();
+    let config_path = temp_dir.path().join(".c2rust").join("config.toml");
+    fs::write(&config_path, "[global]\n\n[model]\n\n[schema]\nalways-array = [\"feature.*.test.args\"]\n").unwrap();
+
+    get_cmd(&temp_dir)
+        .args(["config", "--make", "--create", "--set", "test.args", "-k"])
+        .assert()
+        .success();
+    assert!(read_config(&temp_dir).contains("test.args = [\"-k\"]\n"));
+}
@@ -0,0 +1,226 @@
+use c2rust_config::{Config, ConfigDiff, ConfigError, DiffKind, Entry, EntryValue, Scalar};
+use std::fs;
+use tempfile::TempDir;
+
+/// Helper to write a config.toml into a temp dir and return its path
+fn write_config(temp_dir: &TempDir, content: &str) -> std::path::PathBuf {
+    let config_path = temp_dir.path().join("config.toml");
+    fs::write(&config_path, content).unwrap();
+    config_path
+}
+
+#[test]
+fn test_lib_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = write_config(&temp_dir, "[global]\n\n[model]\n\n[feature.default]\n");
+
+    let mut config = Config::load_from(&config_path).unwrap();
+    config.set("feature.default", "build.cmd", vec!["make".to_string()]).unwrap();
+    config
+        .add("global", "compiler", vec!["gcc".to_string(), "clang".to_string()], None, false)
+        .unwrap();
+    config.del("global", "compiler", vec!["gcc".to_string()], None).unwrap();
+    config.save().unwrap();
+
+    let config = Config::load_from(&config_path).unwrap();
+    assert_eq!(config.list("feature.default", "build.cmd").unwrap(), vec!["make"]);
+    assert_eq!(config.list("global", "compiler").unwrap(), vec!["clang"]);
+    assert_eq!(
+        config.list_all("feature.default").unwrap(),
+        vec![("build.cmd".to_string(), vec!["make".to_string()])]
+    );
+}
+
+#[test]
+fn test_lib_unset_and_missing_key() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = write_config(&temp_dir, "[global]\ncompiler = \"gcc\"\n\n[model]\n");
+
+    let mut config = Config::load_from(&config_path).unwrap();
+    config.unset("global", "compiler").unwrap();
+
+    assert!(matches!(
+        config.list("global", "compiler"),
+        Err(ConfigError::KeyNotFound(_))
+    ));
+}
+
+#[test]
+fn test_lib_normalize_nested_tables() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = write_config(
+        &temp_dir,
+        "[global]\n\n[model]\n\n[feature.default.build]\ncmd = \"make\"\noptions = { debug = true }\n",
+    );
+
+    let config = Config::load_from(&config_path).unwrap();
+    assert_eq!(
+        config.list_all("feature.default").unwrap(),
+        vec![
+            ("build.cmd".to_string(), vec!["make".to_string()]),
+            ("build.options.debug".to_string(), vec!["true".to_string()]),
+        ]
+    );
+}
+
+#[test]
+fn test_resolve_dir_follows_current_dir() {
+    // The only test in this binary that depends on the current directory
+    let first_project = TempDir::new().unwrap();
+    fs::create_dir(first_project.path().join(".c2rust")).unwrap();
+    let second_project = TempDir::new().unwrap();
+    fs::create_dir(second_project.path().join(".c2rust")).unwrap();
+    fs::write(second_project.path().join(".c2rust/config.toml"), "[global]\n\n[model]\n").unwrap();
+
+    std::env::set_current_dir(first_project.path()).unwrap();
+    let first = Config::resolve_dir().unwrap();
+    assert!(first.ends_with(".c2rust"));
+    assert_eq!(Config::resolve_dir().unwrap(), first);
+
+    // Another project is found afresh, and loading reads and writes its own config.toml
+    std::env::set_current_dir(second_project.path()).unwrap();
+    let second = Config::resolve_dir().unwrap();
+    assert_ne!(second, first);
+    assert_eq!(Config::resolve_path().unwrap(), second.join("config.toml"));
+
+    let mut config = Config::load().unwrap();
+    config.set("global", "compiler", vec!["gcc".to_string()]).unwrap();
+    config.save().unwrap();
+    assert_eq!(Config::load().unwrap().list("global", "compiler").unwrap(), vec!["gcc"]);
+    assert!(!first.join("config.toml").exists());
+
+    std::env::set_current_dir(first_project.path()).unwrap();
+    assert_eq!(Config::resolve_dir().unwrap(), first);
+}
+
+#[test]
+fn test_save_error_names_path() {
+    let temp_dir = TempDir::new().unwrap();
+    let c2rust_dir = temp_dir.path().join(".c2rust");
+    fs::create_dir(&c2rust_dir).unwrap();
+    let config_path = c2rust_dir.join("config.toml");
+    fs::write(&config_path, "[global]\n\n[model]\n").unwrap();
+
+    let mut config = Config::load_from(&config_path).unwrap();
+    config.set("global", "compiler", vec!["gcc".to_string()]).unwrap();
+    fs::remove_dir_all(&c2rust_dir).unwrap();
+
+    let err = config.save().unwrap_err();
+    assert!(matches!(err, ConfigError::IoError(_)));
+    assert_eq!(err.exit_code(), 1);
+    let message = err.to_string();
+    assert!(message.contains(&format!("failed to write {}", config_path.display())), "{}", message);
+    assert!(message.contains("no longer exists"), "{}", message);
+}
+
+#[test]
+fn test_diff_against() {
+    let old_dir = TempDir::new().unwrap();
+    let new_dir = TempDir::new().unwrap();
+    let old_path = write_config(
+        &old_dir,
+        "[global]\ncompiler = \"gcc\"\nport = 8080\n\n[model]\nname = \"m\"\n\n[feature.default]\n\"build.cmd\" = \"make\"\n",
+    );
+    let new_path = write_config(
+        &new_dir,
+        "[global]\ncompiler = \"clang\"\nport = \"8080\"\n\n[model]\n\n[feature.default]\n\"build.cmd\" = \"make\"\n\n[feature.debug]\n\"build.dir\" = \"debug\"\n",
+    );
+    let old = Config::load_from(&old_path).unwrap();
+    let new = Config::load_from(&new_path).unwrap();
+
+    let strings = |values: &[&str]| Some(values.iter().map(|v| v.to_string()).collect::<Vec<_>>());
+    assert_eq!(
+        new.diff_against(&old),
+        vec![
+            ConfigDiff {
+                section: "global".to_string(),
+                key: "compiler".to_string(),
+                kind: DiffKind::Changed,
+                old: strings(&["gcc"]),
+                new: strings(&["clang"]),
+            },
+            // Same text, but the type changed from integer to string
+            ConfigDiff {
+                section: "global".to_string(),
+                key: "port".to_string(),
+                kind: DiffKind::Changed,
+                old: strings(&["8080"]),
+                new: strings(&["8080"]),
+            },
+            ConfigDiff {
+                section: "model".to_string(),
+                key: "name".to_string(),
+                kind: DiffKind::Removed,
+                old: strings(&["m"]),
+                new: None,
+            },
+            ConfigDiff {
+                section: "feature.debug".to_string(),
+                key: "build.dir".to_string(),
+                kind: DiffKind::Added,
+                old: None,
+                new: strings(&["debug"]),
+            },
+        ]
+    );
+    assert!(old.diff_against(&old).is_empty());
+}
+
+#[test]
+fn test_entries_keep_types() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = write_config(
+        &temp_dir,
+        "[global]\n\n[model]\nname = \"m\"\nport = 8080\nratio = 0.5\ndebug = true\n\
+         flags = [\"-g\", 2]\nlimits = { cpu = 4 }\n",
+    );
+
+    let config = Config::load_from(&config_path).unwrap();
+    let entry = |key: &str, value, nested| Entry { key: key.to_string(), value, nested };
+    assert_eq!(
+        config.entries("model").unwrap(),
+        vec![
+            entry("name", EntryValue::Scalar(Scalar::String("m".to_string())), false),
+            entry("port", EntryValue::Scalar(Scalar::Integer(8080)), false),
+            entry("ratio", EntryValue::Scalar(Scalar::Float(0.5)), false),
+            entry("debug", EntryValue::Scalar(Scalar::Bool(true)), false),
+            entry(
+                "flags",
+                EntryValue::Array(vec![Scalar::String("-g".to_string()), Scalar::Integer(2)]),
+                false,
+            ),
+            entry("limits.cpu", EntryValue::Scalar(Scalar::Integer(4)), true),
+        ]
+    );
+
+    // list_all renders the same entries as strings
+    assert_eq!(
+        config.list_all("model").unwrap()[4],
+        ("flags".to_string(), vec!["-g".to_string(), "2".to_string()])
+    );
+}
+
+#[test]
+fn test_get_scalar_and_array() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = write_config(
+        &temp_dir,
+        "[global]\ncompiler = [\"gcc\", \"clang\"]\n\n[model]\nname = \"m\"\nport = 8080\nratio = 0.5\ndebug = true\n",
+    );
+
+    let config = Config::load_from(&config_path).unwrap();
+    assert_eq!(config.get_scalar("model", "name").unwrap(), Some(Scalar::String("m".to_string())));
+    assert_eq!(config.get_scalar("model", "port").unwrap(), Some(Scalar::Integer(8080)));
+    assert_eq!(config.get_scalar("model", "ratio").unwrap(), Some(Scalar::Float(0.5)));
+    assert_eq!(config.get_scalar("model", "debug").unwrap(), Some(Scalar::Bool(true)));
+    assert_eq!(config.get_scalar("model", "missing").unwrap(), None);
+    assert_eq!(
+        config.get_array("global", "compiler").unwrap(),
+        Some(vec![Scalar::String("gcc".to_string()), Scalar::String("clang".to_string())])
+    );
+    assert_eq!(config.get_array("global", "missing").unwrap(), None);
+
+    // Asking for the wrong shape is an error rather than a silent conversion
+    assert!(matches!(config.get_scalar("global", "compiler"), Err(ConfigError::InvalidOperation(_))));
+    assert!(matches!(config.get_array("model", "port"), Err(ConfigError::InvalidOperation(_))));
+}